@@ -3,11 +3,19 @@ use std::fmt::Display;
 use owo_colors::style;
 use owo_colors::Style;
 use owo_colors::Styled;
-use smol_str::SmolStr;
+use smol_str::{SmolStr, SmolStrBuilder, ToSmolStr};
+
+use crate::options::Shell;
+use crate::value::Value;
 
 pub struct Chunk<T> {
     icon: Option<Styled<SmolStr>>,
     info: Option<Styled<T>>,
+    value: Option<Value>,
+    /// The URL the info links to (OSC 8).
+    link: Option<SmolStr>,
+    /// The info is past a threshold, and takes the alert style of the segment.
+    alert: bool,
 }
 
 #[derive(Default, Debug)]
@@ -44,6 +52,9 @@ impl<T: Default> Chunk<T> {
         Self {
             icon: Some(style().style(icon.into())),
             info: Some(style().style(info)),
+            value: None,
+            link: None,
+            alert: false,
         }
     }
 
@@ -51,6 +62,9 @@ impl<T: Default> Chunk<T> {
         Self {
             icon: Some(style().style(icon.into())),
             info: None,
+            value: None,
+            link: None,
+            alert: false,
         }
     }
 
@@ -58,26 +72,138 @@ impl<T: Default> Chunk<T> {
         Self {
             icon: None,
             info: Some(style().style(info)),
+            value: None,
+            link: None,
+            alert: false,
         }
     }
 
+    /// Attach the typed value the info is rendered from.
+    pub fn with_value(mut self, value: Value) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Mark the info as past a threshold (e.g. a stale fetch).
+    pub fn with_alert(mut self, alert: bool) -> Self {
+        self.alert = alert;
+        self
+    }
+
+    /// Link the info to the given URL.
+    pub fn with_link(mut self, url: impl Into<SmolStr>) -> Self {
+        self.link = Some(url.into());
+        self
+    }
+
     pub fn with_style(mut self, icon_s: Style, info_s: Style) -> Self {
         let icon = self.icon.as_mut().map(|i| std::mem::take(i.inner_mut()));
         let info = self.info.as_mut().map(|i| std::mem::take(i.inner_mut()));
         Self {
             icon: icon.map(|i| icon_s.style(i)),
             info: info.map(|i| info_s.style(i)),
+            value: self.value,
+            link: self.link,
+            alert: self.alert,
         }
     }
 }
 
+impl<T: Display> Chunk<T> {
+    /// Render the info into a string, keeping the styles.
+    pub fn into_smolstr(self) -> Chunk<SmolStr> {
+        Chunk {
+            icon: self.icon,
+            info: self.info.map(|i| i.style.style(i.inner().to_smolstr())),
+            value: self.value,
+            link: self.link,
+            alert: self.alert,
+        }
+    }
+}
+
+impl Chunk<SmolStr> {
+    /// The icon, without the style.
+    pub fn icon_str(&self) -> Option<&str> {
+        self.icon.as_ref().map(|i| i.inner().as_str())
+    }
+
+    /// The info, without the style.
+    pub fn info_str(&self) -> Option<&str> {
+        self.info.as_ref().map(|i| i.inner().as_str())
+    }
+
+    /// The typed value of the info, if any.
+    pub fn value(&self) -> Option<&Value> {
+        self.value.as_ref()
+    }
+
+    /// Whether the info is past a threshold.
+    pub fn is_alert(&self) -> bool {
+        self.alert
+    }
+
+    /// The URL the info links to, if any.
+    pub fn link(&self) -> Option<&str> {
+        self.link.as_deref()
+    }
+
+    /// Strip the control characters (and thus the escape sequences) from the info and the
+    /// link, so that the output of a provider cannot corrupt the terminal.
+    pub fn sanitize(mut self) -> Self {
+        if let Some(info) = self.info.as_mut() {
+            strip_controls(info.inner_mut());
+        }
+        if let Some(link) = self.link.as_mut() {
+            strip_controls(link);
+        }
+        self
+    }
+
+    /// A copy of the chunk with the info escaped for the prompt of the given shell.
+    pub fn escape(&self, shell: Option<Shell>) -> Self {
+        Self {
+            icon: self.icon.as_ref().map(|i| i.style.style(i.inner().clone())),
+            info: self.info.as_ref().map(|i| {
+                let info = match shell {
+                    Some(shell) => shell.escape(i.inner()),
+                    None => i.inner().clone(),
+                };
+                i.style.style(info)
+            }),
+            value: self.value.clone(),
+            link: self.link.as_ref().map(|link| match shell {
+                Some(shell) => shell.escape(link),
+                None => link.clone(),
+            }),
+            alert: self.alert,
+        }
+    }
+}
+
+fn strip_controls(s: &mut SmolStr) {
+    if s.chars().any(char::is_control) {
+        let mut builder = SmolStrBuilder::new();
+        s.chars()
+            .filter(|c| !c.is_control())
+            .for_each(|c| builder.push(c));
+        *s = builder.finish();
+    }
+}
+
 impl<T: Display> Display for Chunk<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match (&self.icon, &self.info) {
-            (Some(icon), Some(info)) => write!(f, "{icon} {info}"),
-            (Some(icon), None) => write!(f, "{icon}"),
-            (None, Some(info)) => write!(f, "{info}"),
-            (None, None) => Ok(()),
+        match (&self.icon, &self.info, &self.link) {
+            (Some(icon), Some(info), Some(link)) => {
+                write!(f, "{icon} \x1b]8;;{link}\x1b\\{info}\x1b]8;;\x1b\\")
+            }
+            (None, Some(info), Some(link)) => {
+                write!(f, "\x1b]8;;{link}\x1b\\{info}\x1b]8;;\x1b\\")
+            }
+            (Some(icon), Some(info), None) => write!(f, "{icon} {info}"),
+            (Some(icon), None, _) => write!(f, "{icon}"),
+            (None, Some(info), None) => write!(f, "{info}"),
+            (None, None, _) => Ok(()),
         }
     }
 }
@@ -1,50 +1,188 @@
+mod disk;
+#[cfg(test)]
+pub mod fixture;
+mod policy;
+mod runner;
+
 use lazy_static::lazy_static;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use smol_str::SmolStr;
-use std::{collections::HashMap, ffi::OsStr, sync::Arc, task::Poll};
+use std::process::Stdio;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    task::Poll,
+    time::SystemTime,
+};
 use tokio::process::Command;
 
+use crate::commands::daemon;
+
+pub use disk::{cache_dir, DiskCache};
+pub use policy::{ExecPolicy, DEFAULT_POLICY};
+pub use runner::{ProcessRunner, Runner};
+
 lazy_static! {
     pub static ref CMD: CmdCache = CmdCache::new();
 }
 
+/// The commands whose output is determined by the state of the repository alone, as
+/// (command, subcommand). `jj log` only with `--ignore-working-copy`: otherwise it
+/// snapshots the working copy first.
+const PERSISTENT: &[(&str, &str)] = &[
+    ("git", "describe"),
+    ("git", "name-rev"),
+    ("git", "rev-parse"),
+    ("hg", "log"),
+    ("jj", "log"),
+];
+
+/// Whether the output of the command can be reused as long as the state of the repository
+/// is unchanged: the status of the working tree, for one, cannot.
+pub fn is_persistent(cmd: &str, args: &[SmolStr]) -> bool {
+    args.first()
+        .is_some_and(|sub| PERSISTENT.contains(&(cmd, sub.as_str())))
+        && (cmd != "jj" || args.iter().any(|arg| arg == "--ignore-working-copy"))
+}
+
 #[derive(Debug, Clone)]
 struct CmdOutput(Arc<tokio::sync::Mutex<Poll<Option<SmolStr>>>>);
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
-struct CmdKey(&'static str, SmallVec<[SmolStr; 4]>);
+struct CmdKey(Option<PathBuf>, &'static str, SmallVec<[SmolStr; 4]>);
 
 pub struct CmdCache {
+    runner: Box<dyn Runner>,
     cache: Mutex<HashMap<CmdKey, CmdOutput>>,
+    daemon: OnceLock<PathBuf>,
+    disk: OnceLock<DiskCache>,
+    policy: OnceLock<ExecPolicy>,
+    state: Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
 }
 
 impl CmdCache {
     fn new() -> Self {
+        Self::with_runner(Box::new(ProcessRunner))
+    }
+
+    /// A cache whose commands are run by `runner`.
+    pub fn with_runner(runner: Box<dyn Runner>) -> Self {
         Self {
+            runner,
             cache: Mutex::new(std::collections::HashMap::new()),
+            daemon: OnceLock::new(),
+            disk: OnceLock::new(),
+            policy: OnceLock::new(),
+            state: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Persist the outputs of the commands that depend only on the state of the
+    /// repository (e.g. `git describe`) across the prompts.
+    pub fn use_disk_cache(&self, disk: DiskCache) {
+        let _ = self.disk.set(disk);
+    }
+
+    /// Ask the outputs of the commands to the daemon listening on `socket`, running them
+    /// in-process only when the daemon cannot be reached.
+    pub fn use_daemon(&self, socket: PathBuf) {
+        let _ = self.daemon.set(socket);
+    }
+
+    /// Run the commands according to the given policy, instead of the default one.
+    pub fn use_policy(&self, policy: ExecPolicy) {
+        let _ = self.policy.set(policy);
+    }
+
+    /// The state of type `T` shared by the providers during the render (e.g. the parsed
+    /// outputs of the commands), scoped along with the commands in the tests.
+    pub fn state<T: Default + Send + Sync + 'static>(&self) -> Arc<T> {
+        #[cfg(test)]
+        if let Ok(scoped) = fixture::SCOPED.try_with(Arc::clone) {
+            return scoped.local_state();
         }
+        self.local_state()
     }
 
-    fn make_key<I, S>(cmd: &'static str, args: I) -> CmdKey
+    fn local_state<T: Default + Send + Sync + 'static>(&self) -> Arc<T> {
+        let state = Arc::clone(
+            self.state
+                .lock()
+                .entry(TypeId::of::<T>())
+                .or_insert_with(|| Arc::new(T::default())),
+        );
+        state.downcast().expect("state of another type")
+    }
+
+    fn policy(&self) -> &ExecPolicy {
+        self.policy.get().unwrap_or(&DEFAULT_POLICY)
+    }
+
+    /// The key of the command, unless an argument is not valid UTF-8.
+    fn make_key(cwd: Option<&Path>, cmd: &'static str, args: &[OsString]) -> Option<CmdKey> {
+        let args = args
+            .iter()
+            .map(|arg| arg.to_str().map(SmolStr::new))
+            .collect::<Option<SmallVec<[_; 4]>>>()?;
+        Some(CmdKey(cwd.map(Path::to_path_buf), cmd, args))
+    }
+
+    /// The output of the command, run in the current directory.
+    pub async fn exec<I, S>(&self, cmd: &'static str, args: I) -> Option<SmolStr>
+    where
+        I: IntoIterator<Item = S> + Clone,
+        S: AsRef<OsStr>,
+    {
+        self.exec_in(None, cmd, args).await
+    }
+
+    /// The output of the command, run in the directory `cwd` (the current one if `None`).
+    pub async fn exec_in<I, S>(
+        &self,
+        cwd: Option<&Path>,
+        cmd: &'static str,
+        args: I,
+    ) -> Option<SmolStr>
     where
-        I: IntoIterator<Item = S>,
+        I: IntoIterator<Item = S> + Clone,
         S: AsRef<OsStr>,
     {
-        CmdKey(
-            cmd,
-            args.into_iter()
-                .filter_map(|s| s.as_ref().to_str().map(Into::into))
-                .collect::<SmallVec<[_; 4]>>(),
-        )
+        #[cfg(test)]
+        if let Ok(scoped) = fixture::SCOPED.try_with(Arc::clone) {
+            return scoped.exec_cached(cwd, cmd, args).await;
+        }
+        self.exec_cached(cwd, cmd, args).await
     }
 
-    pub async fn exec<I, S>(&self, cmd: &'static str, args: I) -> Option<SmolStr>
+    async fn exec_cached<I, S>(
+        &self,
+        cwd: Option<&Path>,
+        cmd: &'static str,
+        args: I,
+    ) -> Option<SmolStr>
     where
         I: IntoIterator<Item = S> + Clone,
         S: AsRef<OsStr>,
     {
-        let key = Self::make_key(cmd, args.clone());
+        let os_args = args
+            .into_iter()
+            .map(|arg| arg.as_ref().to_os_string())
+            .collect::<SmallVec<[_; 4]>>();
+        // the commands with arguments not valid UTF-8 are run as they are, never cached
+        let Some(key) = Self::make_key(cwd, cmd, &os_args) else {
+            return self.runner.run(cmd, &os_args, cwd, self.policy()).await;
+        };
+        let args = key.2.clone();
+        let disk = self
+            .disk
+            .get()
+            .filter(|_| is_persistent(cmd, &args))
+            .map(|disk| (disk, args.clone()));
         let value = {
             let mut cache = self.cache.lock();
             let value = cache
@@ -57,21 +195,228 @@ impl CmdCache {
         match *value {
             Poll::Ready(ref v) => v.clone(),
             Poll::Pending => {
-                let output = {
-                    let output = Command::new(cmd).args(args).output().await.ok()?;
-                    if output.status.success() {
-                        unsafe {
-                            Some(SmolStr::new(
-                                std::str::from_utf8_unchecked(&output.stdout).trim_end(),
-                            ))
-                        }
-                    } else {
-                        None
+                let persisted = match disk {
+                    Some((disk, args)) => cwd
+                        .map(Path::to_path_buf)
+                        .or_else(|| std::env::current_dir().ok())
+                        .and_then(|cwd| RepoState::of(&cwd).map(|state| (disk, args, cwd, state))),
+                    None => None,
+                };
+                if let Some((disk, args, cwd, state)) = &persisted {
+                    if let Some(output) = disk.get(cwd, cmd, args, state).await {
+                        *value = Poll::Ready(output.clone());
+                        return output;
                     }
+                }
+
+                // in debug mode the failures are reported by the commands run in-process
+                let remote = match self
+                    .daemon
+                    .get()
+                    .filter(|_| daemon::serves(cmd, &args) && !self.policy().debug())
+                {
+                    Some(socket) => daemon::request(socket, cwd, cmd, &args, self.policy())
+                        .await
+                        .ok(),
+                    None => None,
+                };
+                let output = match remote {
+                    Some(output) => output,
+                    None => self.runner.run(cmd, &os_args, cwd, self.policy()).await,
                 };
+                if let Some((disk, args, cwd, state)) = persisted {
+                    let _ = disk.put(&cwd, cmd, &args, state, output.clone()).await;
+                }
                 *value = Poll::Ready(output.clone());
                 output
             }
         }
     }
 }
+
+/// Run the command (in the directory `cwd`, if given) according to the policy and
+/// return its output, trimmed, if it succeeds. The command is killed when it exceeds its
+/// timeout, if it has one.
+pub async fn run<I, S>(
+    cmd: &str,
+    args: I,
+    cwd: Option<&Path>,
+    policy: &ExecPolicy,
+) -> Option<SmolStr>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let args = args
+        .into_iter()
+        .map(|a| a.as_ref().to_os_string())
+        .collect::<SmallVec<[_; 4]>>();
+    let timeout = policy.timeout(cmd, args.first().and_then(|a| a.to_str()));
+
+    let mut command = Command::new(cmd);
+    command
+        .args(&args)
+        .envs(policy.env())
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+
+    let describe = || {
+        std::iter::once(cmd.into())
+            .chain(args.iter().map(|a| a.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let output = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, command.output())
+            .await
+            .map_err(|_| timeout),
+        None => Ok(command.output().await),
+    };
+    let output = match output {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            if policy.debug() {
+                eprintln!("auraline: `{}`: {e}", describe());
+            }
+            return None;
+        }
+        Err(timeout) => {
+            if policy.debug() {
+                eprintln!("auraline: `{}` killed after {timeout:?}", describe());
+            }
+            return None;
+        }
+    };
+
+    if output.status.success() {
+        Some(SmolStr::new(
+            String::from_utf8_lossy(&output.stdout).trim_end(),
+        ))
+    } else {
+        if policy.debug() {
+            eprintln!(
+                "auraline: `{}` failed ({}): {}",
+                describe(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+        None
+    }
+}
+
+/// The state of the repository containing a directory: the modification times of the
+/// metadata files that change whenever the outputs of the VCS commands may change
+/// (e.g. `HEAD`, the index, the refs and the reflogs in git).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RepoState {
+    root: PathBuf,
+    mtimes: SmallVec<[Option<SystemTime>; 16]>,
+}
+
+impl RepoState {
+    /// The state of the repository containing `dir`, if any.
+    pub fn of(dir: &Path) -> Option<Self> {
+        dir.ancestors().find_map(|root| {
+            let files = if let Some(git_dir) = git_dir(root) {
+                let common_dir = std::fs::read_to_string(git_dir.join("commondir"))
+                    .map(|common| git_dir.join(common.trim()))
+                    .unwrap_or_else(|_| git_dir.clone());
+                const GIT_FILES: &[&str] = &["HEAD", "index", "logs/HEAD", "FETCH_HEAD"];
+                const GIT_COMMON_FILES: &[&str] = &[
+                    "config",
+                    "packed-refs",
+                    "refs/heads",
+                    "refs/tags",
+                    "refs/remotes",
+                    "refs/stash",
+                    "logs/refs/stash",
+                    "worktrees",
+                ];
+                // the refs fetched from each remote
+                let mut remotes = std::fs::read_dir(common_dir.join("refs/remotes"))
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| Some(entry.ok()?.path()))
+                    .collect::<SmallVec<[_; 4]>>();
+                remotes.sort();
+                GIT_FILES
+                    .iter()
+                    .map(|f| git_dir.join(f))
+                    .chain(GIT_COMMON_FILES.iter().map(|f| common_dir.join(f)))
+                    .chain(remotes)
+                    .collect::<SmallVec<[_; 16]>>()
+            } else if root.join(".jj").is_dir() {
+                [".jj/repo/op_heads/heads", ".jj/working_copy/checkout"]
+                    .iter()
+                    .map(|f| root.join(f))
+                    .collect()
+            } else if root.join(".hg").is_dir() {
+                [
+                    "dirstate",
+                    "branch",
+                    "bookmarks",
+                    "bookmarks.current",
+                    "sharedpath",
+                ]
+                .iter()
+                .map(|f| root.join(".hg").join(f))
+                .collect()
+            } else if root.join(".pijul").is_dir() {
+                [".pijul", ".pijul/pristine"]
+                    .iter()
+                    .map(|f| root.join(f))
+                    .collect()
+            } else if root.join("_darcs").is_dir() {
+                ["_darcs/hashed_inventory", "_darcs/index"]
+                    .iter()
+                    .map(|f| root.join(f))
+                    .collect()
+            } else {
+                return None;
+            };
+
+            Some(Self {
+                root: root.to_path_buf(),
+                mtimes: files
+                    .iter()
+                    .map(|f| std::fs::metadata(f).and_then(|m| m.modified()).ok())
+                    .collect(),
+            })
+        })
+    }
+}
+
+/// The git directory of the working tree `root`: either `.git` or the directory it
+/// points to (in linked worktrees and submodules).
+pub fn git_dir(root: &Path) -> Option<PathBuf> {
+    let dot_git = root.join(".git");
+    let metadata = std::fs::metadata(&dot_git).ok()?;
+    if metadata.is_dir() {
+        return Some(dot_git);
+    }
+
+    let content = std::fs::read_to_string(&dot_git).ok()?;
+    let path = content.strip_prefix("gitdir:")?.trim();
+    Some(root.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::ffi::OsStrExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_exec_non_utf8() {
+        // passed as they are, not dropped from the command line
+        let cache = CmdCache::new();
+        let arg = OsStr::from_bytes(b"a\xffb");
+        let output = cache.exec("printf", [OsStr::new("%s"), arg]).await;
+        assert_eq!(output.as_deref(), Some("a\u{FFFD}b"));
+    }
+}
@@ -0,0 +1,175 @@
+use std::hash::Hasher;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use fnv::FnvHasher;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use smol_str::SmolStr;
+
+use crate::cmd::RepoState;
+
+/// The directory of the files auraline keeps across the prompts: `$XDG_CACHE_HOME/auraline`,
+/// or `~/.cache/auraline`.
+pub fn cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::home_dir().map(|home| home.join(".cache")))
+        .map(|dir| dir.join("auraline"))
+}
+
+/// A cache of the outputs of the commands, persisted across the prompts in
+/// `$XDG_CACHE_HOME/auraline/outputs`: one file per repository, whose outputs are valid as
+/// long as the state of the repository is unchanged.
+pub struct DiskCache {
+    dir: PathBuf,
+    /// Held while updating a file, so that the outputs of the commands of the prompt do not
+    /// overwrite each other.
+    lock: tokio::sync::Mutex<()>,
+}
+
+/// The outputs kept per repository: the commands of the prompt hardly exceed a dozen.
+const MAX_ENTRIES: usize = 64;
+
+/// The files of the repositories not visited for this long are removed...
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// ... at most once in this interval.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Key {
+    cwd: PathBuf,
+    cmd: SmolStr,
+    args: SmallVec<[SmolStr; 4]>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    key: Key,
+    output: Option<SmolStr>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Outputs {
+    state: RepoState,
+    entries: Vec<Entry>,
+}
+
+impl DiskCache {
+    /// The cache in the user cache directory, if any.
+    pub fn new() -> Option<Self> {
+        cache_dir().map(|dir| Self {
+            dir: dir.join("outputs"),
+            lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// The output of the command run in `cwd` with the repository in the given state,
+    /// if cached.
+    pub async fn get(
+        &self,
+        cwd: &Path,
+        cmd: &str,
+        args: &[SmolStr],
+        state: &RepoState,
+    ) -> Option<Option<SmolStr>> {
+        let key = Key::new(cwd, cmd, args);
+        let outputs = self.read(state).await?;
+        (outputs.state == *state)
+            .then(|| outputs.entries.into_iter().find(|e| e.key == key))
+            .flatten()
+            .map(|e| e.output)
+    }
+
+    /// Store the output of the command run in `cwd` with the repository in the given state,
+    /// dropping the outputs stored in another state.
+    pub async fn put(
+        &self,
+        cwd: &Path,
+        cmd: &str,
+        args: &[SmolStr],
+        state: RepoState,
+        output: Option<SmolStr>,
+    ) -> std::io::Result<()> {
+        let key = Key::new(cwd, cmd, args);
+        let path = self.path(&state);
+        let _lock = self.lock.lock().await;
+        let mut outputs = match self.read(&state).await {
+            Some(outputs) if outputs.state == state => outputs,
+            _ => Outputs {
+                state,
+                entries: Vec::new(),
+            },
+        };
+        outputs.entries.retain(|e| e.key != key);
+        if outputs.entries.len() >= MAX_ENTRIES {
+            outputs.entries.remove(0);
+        }
+        outputs.entries.push(Entry { key, output });
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        self.prune().await;
+        // write and rename, so that a concurrent prompt never reads a partial file
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        tokio::fs::write(&tmp, serde_json::to_vec(&outputs)?).await?;
+        tokio::fs::rename(&tmp, &path).await
+    }
+
+    async fn read(&self, state: &RepoState) -> Option<Outputs> {
+        let content = tokio::fs::read(self.path(state)).await.ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    /// Remove the files of the repositories not visited recently, along with the
+    /// temporary files of the prompts interrupted while writing.
+    async fn prune(&self) {
+        let stamp = self.dir.join(".pruned");
+        if age(&stamp).await.is_some_and(|age| age < PRUNE_INTERVAL) {
+            return;
+        }
+        if tokio::fs::write(&stamp, b"").await.is_err() {
+            return;
+        }
+
+        let Ok(mut entries) = tokio::fs::read_dir(&self.dir).await else {
+            return;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path != stamp && age(&path).await.is_some_and(|age| age > MAX_AGE) {
+                let _ = tokio::fs::remove_file(&path).await;
+            }
+        }
+    }
+
+    /// The file of the repository, named after a hash of its root stable across the builds
+    /// of auraline.
+    fn path(&self, state: &RepoState) -> PathBuf {
+        let mut hasher = FnvHasher::default();
+        hasher.write(state.root.as_os_str().as_bytes());
+        self.dir.join(format!("{:016x}.json", hasher.finish()))
+    }
+}
+
+impl Key {
+    fn new(cwd: &Path, cmd: &str, args: &[SmolStr]) -> Self {
+        Self {
+            cwd: cwd.to_path_buf(),
+            cmd: cmd.into(),
+            args: args.iter().cloned().collect(),
+        }
+    }
+}
+
+async fn age(path: &Path) -> Option<Duration> {
+    tokio::fs::metadata(path)
+        .await
+        .ok()?
+        .modified()
+        .ok()?
+        .elapsed()
+        .ok()
+}
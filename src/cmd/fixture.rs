@@ -0,0 +1,61 @@
+//! Recorded outputs of the commands, answering in place of the real ones in the tests.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use itertools::Itertools;
+use smol_str::SmolStr;
+
+use crate::cmd::{CmdCache, ExecPolicy, Runner};
+
+tokio::task_local! {
+    /// The cache answering in place of `CMD` within [`Fixture::scope`].
+    pub static SCOPED: Arc<CmdCache>;
+}
+
+/// A runner answering with the recorded outputs of the command lines; the commands not
+/// recorded fail.
+#[derive(Default)]
+pub struct Fixture {
+    outputs: HashMap<SmolStr, SmolStr>,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the output of the command line (e.g. `git status --porcelain`).
+    pub fn with(mut self, line: &str, output: &str) -> Self {
+        self.outputs.insert(line.into(), output.into());
+        self
+    }
+
+    /// Await `f`, with the commands answered by the fixture in place of `CMD`.
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        let cache = CmdCache::with_runner(Box::new(self));
+        SCOPED.scope(Arc::new(cache), f).await
+    }
+}
+
+impl Runner for Fixture {
+    fn run<'a>(
+        &'a self,
+        cmd: &'a str,
+        args: &'a [OsString],
+        _cwd: Option<&'a Path>,
+        _policy: &'a ExecPolicy,
+    ) -> BoxFuture<'a, Option<SmolStr>> {
+        let line: SmolStr = std::iter::once(cmd.into())
+            .chain(args.iter().map(|arg| arg.to_string_lossy()))
+            .join(" ")
+            .into();
+        let output = self.outputs.get(&line).map(|o| o.trim_end().into());
+        async move { output }.boxed()
+    }
+}
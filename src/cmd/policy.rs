@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+use crate::config::ExecSettings;
+
+/// The environment every command runs with: an untranslated output, no lock taken on
+/// the repository behind the user's back and no prompt for credentials.
+const ENV: &[(&str, &str)] = &[
+    ("LC_ALL", "C"),
+    ("GIT_OPTIONAL_LOCKS", "0"),
+    ("GIT_TERMINAL_PROMPT", "0"),
+    ("HGPLAIN", "1"),
+];
+
+/// How the commands are run.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ExecPolicy {
+    timeout: Option<Duration>,
+    timeouts: BTreeMap<SmolStr, Duration>,
+    env: BTreeMap<SmolStr, SmolStr>,
+    debug: bool,
+}
+
+pub static DEFAULT_POLICY: ExecPolicy = ExecPolicy::new();
+
+impl ExecPolicy {
+    pub const fn new() -> Self {
+        Self {
+            timeout: None,
+            timeouts: BTreeMap::new(),
+            env: BTreeMap::new(),
+            debug: false,
+        }
+    }
+
+    /// The policy of the `[exec]` settings; in debug mode the failures of the commands
+    /// are reported on stderr.
+    pub fn from_settings(settings: &ExecSettings, debug: bool) -> Self {
+        Self {
+            timeout: settings.timeout,
+            timeouts: settings.timeouts.clone(),
+            env: settings.env.clone(),
+            debug,
+        }
+    }
+
+    /// The timeout of the command: that of `cmd subcommand`, of `cmd`, or the default one,
+    /// if any.
+    pub fn timeout(&self, cmd: &str, subcommand: Option<&str>) -> Option<Duration> {
+        subcommand
+            .and_then(|sub| self.timeouts.get(format!("{cmd} {sub}").as_str()))
+            .or_else(|| self.timeouts.get(cmd))
+            .copied()
+            .or(self.timeout)
+    }
+
+    /// The environment variables set for the commands, the configured ones overriding
+    /// the defaults.
+    pub fn env(&self) -> impl Iterator<Item = (&str, &str)> {
+        ENV.iter()
+            .copied()
+            .filter(|(k, _)| !self.env.contains_key(*k))
+            .chain(self.env.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+    }
+
+    pub fn debug(&self) -> bool {
+        self.debug
+    }
+}
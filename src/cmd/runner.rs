@@ -0,0 +1,36 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use smol_str::SmolStr;
+
+use crate::cmd::{run, ExecPolicy};
+
+/// Runs the commands whose outputs are cached by [`CmdCache`](crate::cmd::CmdCache).
+pub trait Runner: Send + Sync {
+    /// The output of the command run in the directory `cwd` (the current one if `None`),
+    /// if it succeeds.
+    fn run<'a>(
+        &'a self,
+        cmd: &'a str,
+        args: &'a [OsString],
+        cwd: Option<&'a Path>,
+        policy: &'a ExecPolicy,
+    ) -> BoxFuture<'a, Option<SmolStr>>;
+}
+
+/// Runs the commands as child processes.
+pub struct ProcessRunner;
+
+impl Runner for ProcessRunner {
+    fn run<'a>(
+        &'a self,
+        cmd: &'a str,
+        args: &'a [OsString],
+        cwd: Option<&'a Path>,
+        policy: &'a ExecPolicy,
+    ) -> BoxFuture<'a, Option<SmolStr>> {
+        run(cmd, args, cwd, policy).boxed()
+    }
+}
@@ -1,3 +1,5 @@
+pub mod daemon;
+pub mod fetch;
 pub mod init;
 pub mod profile;
 pub mod prompt;
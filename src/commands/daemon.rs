@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::DirBuilder;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::cmd::{is_persistent, run, ExecPolicy, RepoState};
+use crate::options::DaemonOptions;
+
+/// The outputs of the commands are reused for this long, unless the state of the
+/// repository changes earlier.
+const DEFAULT_TTL: Duration = Duration::from_secs(5);
+
+/// Beyond this number of entries the expired ones are evicted.
+const MAX_ENTRIES: usize = 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    cwd: PathBuf,
+    cmd: SmolStr,
+    args: Vec<SmolStr>,
+    /// The policy of the client, for the command to run in its environment.
+    policy: ExecPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Response {
+    Output(Option<SmolStr>),
+    /// The command is left to the client (e.g. run in another container than the daemon).
+    Refused,
+}
+
+/// The commands the daemon serves, by what their output depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Served {
+    /// The state of the repository alone (e.g. `git describe`).
+    Repository,
+    /// The network namespace of the client (`ip netns identify`).
+    NetworkNamespace,
+    /// The container the client runs in (`systemd-detect-virt`).
+    Container,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    scope: Scope,
+    cmd: SmolStr,
+    args: Vec<SmolStr>,
+    policy: ExecPolicy,
+}
+
+/// What an output is reused for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Scope {
+    /// The commands run in the directory.
+    Dir(PathBuf),
+    /// The clients in the namespace, identified by the device and the inode of its file.
+    Namespace(u64, u64),
+    /// The clients in the namespaces of the daemon.
+    Daemon,
+}
+
+struct Entry {
+    state: Option<RepoState>,
+    time: Instant,
+    output: Option<SmolStr>,
+}
+
+struct Cache {
+    ttl: Duration,
+    entries: Mutex<HashMap<Key, Entry>>,
+}
+
+/// The path of the socket of the daemon, in `$XDG_RUNTIME_DIR`, or else in a directory
+/// private to the user in `/tmp`.
+pub fn socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| PathBuf::from(format!("/tmp/auraline-{}", uid())))
+        .join("auraline.sock")
+}
+
+/// The socket of the daemon, if one may be listening in a directory private to the user.
+pub fn find_socket() -> Option<PathBuf> {
+    let path = socket_path();
+    (is_private(path.parent()?) && path.exists()).then_some(path)
+}
+
+/// Whether the directory is owned by the user and inaccessible to the others, so that
+/// no other user can put a socket of their own in place of the daemon's.
+fn is_private(dir: &Path) -> bool {
+    std::fs::symlink_metadata(dir)
+        .is_ok_and(|m| m.is_dir() && m.uid() == uid() && m.mode() & 0o077 == 0)
+}
+
+/// Whether the other end of the connection is run by the same user.
+fn is_same_user(stream: &UnixStream) -> bool {
+    stream.peer_cred().is_ok_and(|cred| cred.uid() == uid())
+}
+
+fn uid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+/// Whether the daemon serves the output of the command, which the others read from the
+/// working tree or from the environment of the client.
+pub fn serves(cmd: &str, args: &[SmolStr]) -> bool {
+    Served::of(cmd, args).is_some()
+}
+
+impl Served {
+    fn of(cmd: &str, args: &[SmolStr]) -> Option<Self> {
+        match (cmd, args) {
+            _ if is_persistent(cmd, args) => Some(Self::Repository),
+            ("ip", [netns, identify]) if netns == "netns" && identify == "identify" => {
+                Some(Self::NetworkNamespace)
+            }
+            ("systemd-detect-virt", []) => Some(Self::Container),
+            _ => None,
+        }
+    }
+}
+
+/// The identity of the namespace `ns` (e.g. `net`) of the process `pid`.
+async fn namespace(pid: u32, ns: &str) -> Option<(u64, u64)> {
+    let metadata = tokio::fs::metadata(format!("/proc/{pid}/ns/{ns}"))
+        .await
+        .ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Whether the process `pid` sees the same processes, mounts and cgroups as the daemon,
+/// as in the same container.
+async fn shares_container(pid: u32) -> bool {
+    for ns in ["pid", "mnt", "cgroup"] {
+        let theirs = namespace(pid, ns).await;
+        if theirs.is_none() || theirs != namespace(std::process::id(), ns).await {
+            return false;
+        }
+    }
+    true
+}
+
+/// Ask the daemon listening on `socket` for the output of the command, run in the
+/// directory `cwd` (the current one if `None`) according to the policy.
+pub async fn request<I, S>(
+    socket: &Path,
+    cwd: Option<&Path>,
+    cmd: &str,
+    args: I,
+    policy: &ExecPolicy,
+) -> anyhow::Result<Option<SmolStr>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let request = Request {
+        cwd: match cwd {
+            Some(cwd) => std::env::current_dir()?.join(cwd),
+            None => std::env::current_dir()?,
+        },
+        cmd: cmd.into(),
+        args: args
+            .into_iter()
+            .map(|a| a.as_ref().to_string_lossy().into())
+            .collect(),
+        policy: policy.clone(),
+    };
+
+    // a busy daemon costs no more than running the command in-process
+    let sub = request.args.first().map(SmolStr::as_str);
+    match policy.timeout(cmd, sub) {
+        Some(timeout) => tokio::time::timeout(timeout, exchange(socket, &request))
+            .await
+            .context("the daemon did not answer in time")?,
+        None => exchange(socket, &request).await,
+    }
+}
+
+async fn exchange(socket: &Path, request: &Request) -> anyhow::Result<Option<SmolStr>> {
+    let mut stream = UnixStream::connect(socket).await?;
+    anyhow::ensure!(is_same_user(&stream), "the daemon is run by another user");
+
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    stream.write_all(&line).await?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).await?;
+    match serde_json::from_str(&response)? {
+        Response::Output(output) => Ok(output),
+        Response::Refused => anyhow::bail!("the daemon left the command to the client"),
+    }
+}
+
+/// Run the daemon, serving the outputs of the commands to the clients until killed.
+pub async fn run_daemon(opts: DaemonOptions) -> anyhow::Result<()> {
+    let path = socket_path();
+    let dir = path.parent().context("invalid socket path")?;
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Err(e) if e.kind() != ErrorKind::AlreadyExists => {
+            return Err(e).with_context(|| format!("cannot create '{}'", dir.display()));
+        }
+        _ => {}
+    }
+    // the socket is only reachable by the user, from the moment it is bound
+    anyhow::ensure!(
+        is_private(dir),
+        "'{}' is accessible to other users",
+        dir.display()
+    );
+
+    if UnixStream::connect(&path).await.is_ok() {
+        anyhow::bail!("a daemon is already listening on '{}'", path.display());
+    }
+
+    // the socket of a daemon that did not shut down cleanly
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("cannot listen on '{}'", path.display()))?;
+
+    let cache = Arc::new(Cache {
+        ttl: opts.ttl.unwrap_or(DEFAULT_TTL),
+        entries: Mutex::new(HashMap::new()),
+    });
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let result = tokio::select! {
+        result = accept(listener, cache) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+        _ = terminate.recv() => Ok(()),
+    };
+
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+async fn accept(listener: UnixListener, cache: Arc<Cache>) -> anyhow::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        if !is_same_user(&stream) {
+            continue;
+        }
+        let cache = Arc::clone(&cache);
+        tokio::spawn(async move {
+            if let Err(e) = serve(stream, &cache).await {
+                eprintln!("auraline daemon: {e:#}");
+            }
+        });
+    }
+}
+
+async fn serve(stream: UnixStream, cache: &Cache) -> anyhow::Result<()> {
+    let pid = stream
+        .peer_cred()
+        .ok()
+        .and_then(|cred| cred.pid())
+        .and_then(|pid| u32::try_from(pid).ok());
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    if BufReader::new(reader).read_line(&mut line).await? == 0 {
+        // a connection probing whether the daemon is running
+        return Ok(());
+    }
+    let request: Request = serde_json::from_str(&line).context("invalid request")?;
+
+    let key = |scope| Key {
+        scope,
+        cmd: request.cmd.clone(),
+        args: request.args.clone(),
+        policy: request.policy.clone(),
+    };
+    let output = match (Served::of(&request.cmd, &request.args), pid) {
+        (Some(Served::Repository), _) => {
+            let cwd = request.cwd.clone();
+            let state = tokio::task::spawn_blocking(move || RepoState::of(&cwd)).await?;
+            let run = run(
+                &request.cmd,
+                &request.args,
+                Some(&request.cwd),
+                &request.policy,
+            );
+            let key = key(Scope::Dir(request.cwd.clone()));
+            Some(cache.output(key, state, run).await)
+        }
+        // identified by the namespace of the client rather than by that of the daemon
+        (Some(Served::NetworkNamespace), Some(pid)) => match namespace(pid, "net").await {
+            Some((dev, ino)) => {
+                let pid = pid.to_string();
+                let args = ["netns", "identify", pid.as_str()];
+                let run = run("ip", args, None, &request.policy);
+                Some(
+                    cache
+                        .output(key(Scope::Namespace(dev, ino)), None, run)
+                        .await,
+                )
+            }
+            None => None,
+        },
+        (Some(Served::Container), Some(pid)) if shares_container(pid).await => {
+            let run = run(&request.cmd, &request.args, None, &request.policy);
+            Some(cache.output(key(Scope::Daemon), None, run).await)
+        }
+        _ => None,
+    };
+
+    let response = match output {
+        Some(output) => Response::Output(output),
+        None => Response::Refused,
+    };
+    let mut response = serde_json::to_vec(&response)?;
+    response.push(b'\n');
+    writer.write_all(&response).await?;
+    Ok(())
+}
+
+impl Cache {
+    /// The output of the command, reused while fresh and the state of the repository
+    /// (if any) is unchanged, else awaited from `run`.
+    async fn output(
+        &self,
+        key: Key,
+        state: Option<RepoState>,
+        run: impl Future<Output = Option<SmolStr>>,
+    ) -> Option<SmolStr> {
+        if let Some(entry) = self.entries.lock().get(&key) {
+            if entry.state == state && entry.time.elapsed() < self.ttl {
+                return entry.output.clone();
+            }
+        }
+
+        let output = run.await;
+
+        let mut entries = self.entries.lock();
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, e| e.time.elapsed() < self.ttl);
+        }
+        entries.insert(
+            key,
+            Entry {
+                state,
+                time: Instant::now(),
+                output: output.clone(),
+            },
+        );
+        output
+    }
+}
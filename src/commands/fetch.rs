@@ -0,0 +1,125 @@
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::ErrorKind;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use fnv::FnvHasher;
+use scopeguard::defer;
+
+use crate::cmd::{cache_dir, ExecPolicy};
+use crate::options::{FetchOptions, Options};
+use crate::providers::vcs::{Vcs, VcsTrait};
+
+/// A fetch still running after this long is killed, and its lock is considered stale.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Launch the fetch of the remotes of the repository in `root`, detached from the prompt,
+/// unless one was launched less than `--auto-fetch` ago or is still running.
+pub fn spawn_fetch(opts: &Options, vcs: &Vcs, root: &Path) -> std::io::Result<()> {
+    let (Some(interval), Some(command)) = (opts.auto_fetch, vcs.fetch_command()) else {
+        return Ok(());
+    };
+    let Some(dir) = cache_dir().map(|dir| dir.join("fetch")) else {
+        return Ok(());
+    };
+
+    let (stamp, lock) = paths(&dir, root);
+    if age(&stamp).is_some_and(|age| age < interval)
+        || age(&lock).is_some_and(|age| age < FETCH_TIMEOUT)
+    {
+        return Ok(());
+    }
+
+    // renewed on launch, so that an unreachable remote is tried once per interval
+    std::fs::create_dir_all(&dir)?;
+    File::create(&stamp)?.set_modified(SystemTime::now())?;
+
+    let policy = ExecPolicy::from_settings(&opts.settings.exec, opts.debug);
+    let mut cmd = Command::new(std::env::current_exe()?);
+    cmd.arg("fetch")
+        .arg("--lock")
+        .arg(&lock)
+        .arg("--")
+        .args(command)
+        .current_dir(root)
+        .envs(policy.env())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    // in a session of its own, out of reach of the signals of the terminal, and never
+    // waited for
+    unsafe {
+        cmd.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+    cmd.spawn().map(drop)
+}
+
+/// Run the fetch command while holding the lock, so that the prompts of the other shells
+/// in the same repository do not fetch concurrently.
+pub async fn run_fetch(opts: FetchOptions) -> anyhow::Result<()> {
+    let (program, args) = opts
+        .command
+        .split_first()
+        .context("missing fetch command")?;
+
+    if !acquire(&opts.lock).with_context(|| format!("cannot lock '{}'", opts.lock.display()))? {
+        return Ok(());
+    }
+    defer! {
+        let _ = std::fs::remove_file(&opts.lock);
+    }
+
+    let mut child = tokio::process::Command::new(program)
+        .args(args)
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("cannot run '{program}'"))?;
+    let _ = tokio::time::timeout(FETCH_TIMEOUT, child.wait()).await;
+    Ok(())
+}
+
+/// Create the lock file, replacing one left by a fetch that did not complete. `false`
+/// when another fetch holds it.
+fn acquire(lock: &Path) -> std::io::Result<bool> {
+    match File::create_new(lock) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            if age(lock).is_some_and(|age| age < FETCH_TIMEOUT) {
+                return Ok(false);
+            }
+            std::fs::remove_file(lock)?;
+            Ok(File::create_new(lock).is_ok())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// The stamp of the last launch and the lock of the fetch of the repository in `root`,
+/// named after a hash of the root stable across the builds of auraline.
+fn paths(dir: &Path, root: &Path) -> (PathBuf, PathBuf) {
+    let mut hasher = FnvHasher::default();
+    hasher.write(root.as_os_str().as_bytes());
+    let name = format!("{:016x}", hasher.finish());
+    (
+        dir.join(format!("{name}.stamp")),
+        dir.join(format!("{name}.lock")),
+    )
+}
+
+fn age(path: &Path) -> Option<Duration> {
+    std::fs::metadata(path)
+        .ok()?
+        .modified()
+        .ok()?
+        .elapsed()
+        .ok()
+}
@@ -4,6 +4,7 @@ use phf::phf_map;
 static INIT_MAP: phf::Map<&'static str, &'static str> = phf_map! {
     "bash" => include_str!("scripts/init.bash"),
     "zsh" => include_str!("scripts/init.zsh"),
+    "fish" => include_str!("scripts/init.fish"),
 };
 
 pub fn print_init(opts: InitOptions) {
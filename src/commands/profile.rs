@@ -1,3 +1,6 @@
+use std::collections::BTreeMap;
+
+use crate::config::Settings;
 use crate::options::Options;
 use smol_str::SmolStr;
 
@@ -20,10 +23,30 @@ const MINIMAL: Options = Options {
     manifest: false,
     vcs: false,
     duration: false,
+    clock: false,
     theme: None,
     timings: false,
+    debug: false,
     nerd_font: false,
+    hyperlinks: false,
     exit_code: Some(0),
+    order: Vec::new(),
+    right: Vec::new(),
+    rprompt: false,
+    template: None,
+    shell: None,
+    format: None,
+    timeout: None,
+    client: false,
+    disk_cache: false,
+    sysroot: None,
+    git_backend: None,
+    base_branch: None,
+    lfs: false,
+    commit_summary: false,
+    auto_fetch: None,
+    subsegments: BTreeMap::new(),
+    settings: Settings::new(),
 };
 
 const LEAN: Options = Options {
@@ -45,10 +68,30 @@ const LEAN: Options = Options {
     manifest: false,
     vcs: false,
     duration: true,
+    clock: false,
     theme: None,
     timings: false,
+    debug: false,
     nerd_font: false,
+    hyperlinks: false,
     exit_code: None,
+    order: Vec::new(),
+    right: Vec::new(),
+    rprompt: false,
+    template: None,
+    shell: None,
+    format: None,
+    timeout: None,
+    client: false,
+    disk_cache: false,
+    sysroot: None,
+    git_backend: None,
+    base_branch: None,
+    lfs: false,
+    commit_summary: false,
+    auto_fetch: None,
+    subsegments: BTreeMap::new(),
+    settings: Settings::new(),
 };
 
 const NERDY: Options = Options {
@@ -70,10 +113,30 @@ const NERDY: Options = Options {
     manifest: true,
     vcs: true,
     duration: true,
+    clock: false,
     theme: None,
     timings: false,
+    debug: false,
     nerd_font: true,
+    hyperlinks: false,
     exit_code: None,
+    order: Vec::new(),
+    right: Vec::new(),
+    rprompt: false,
+    template: None,
+    shell: None,
+    format: None,
+    timeout: None,
+    client: false,
+    disk_cache: false,
+    sysroot: None,
+    git_backend: None,
+    base_branch: None,
+    lfs: false,
+    commit_summary: false,
+    auto_fetch: None,
+    subsegments: BTreeMap::new(),
+    settings: Settings::new(),
 };
 
 pub fn get_profile(name: &str) -> Option<Options> {
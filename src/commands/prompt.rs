@@ -4,9 +4,12 @@ use frunk::HCons;
 use frunk::HNil;
 
 use frunk::Poly;
+use futures::FutureExt;
 use std::env;
 use std::fmt::Display;
+use std::fmt::Write;
 use std::future::Future;
+use std::io::Write as _;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::task::JoinError;
@@ -18,10 +21,12 @@ use crate::providers::basic::hostname;
 use crate::providers::basic::pwd;
 use crate::providers::basic::realname;
 use crate::providers::basic::user;
+use crate::providers::clock::show as clock_show;
 use crate::providers::duration::show as duration_show;
 use crate::providers::exit_code::show as exit_code_show;
 
 use crate::chunk::Chunk;
+use crate::commands::fetch;
 use crate::providers::huge_pages::show as huge_pages_show;
 use crate::providers::manifest::show as manifest_show;
 use crate::providers::memory::show as memory_show;
@@ -32,47 +37,110 @@ use crate::providers::ssh::show as ssh_show;
 use crate::providers::vcs::{infer_vcs, Vcs, VcsTrait};
 use crate::providers::virt::show as virt_show;
 
-use crate::style::build_color_style;
+use crate::options::{belongs_to, Format, Shell};
+use crate::style::{build_color_style, visible_width, wrap_escapes};
+use crate::value::Value;
 
 use crate::Options;
+use itertools::Itertools;
 use owo_colors::Style;
+use serde::Serialize;
+use smol_str::SmolStr;
 
 macro_rules! item {
-    ($provider:expr, $opt:expr, $style:expr) => {{
+    ($name:literal, $provider:expr, $opt:expr, $style:expr) => {{
         let cloned_opts = Arc::clone(&$opt);
-        let style = $style;
+        let style = $opt
+            .settings
+            .segment_style($name, $opt.theme.as_deref(), $style);
         tokio::spawn(async move {
             let begin = std::time::Instant::now();
-            let res = $provider(&cloned_opts)
-                .await
-                .map(|c| c.with_style(style.0, style.1));
-            (provider_name(&$provider), begin.elapsed(), res)
+            let (res, timed_out) =
+                within_budget($name, &cloned_opts, $provider(&cloned_opts)).await;
+            let res = res.map(|c| c.with_style(style.0, style.1));
+            (
+                $name,
+                provider_name(&$provider),
+                begin.elapsed(),
+                res,
+                timed_out,
+            )
         })
     }};
 }
 
 macro_rules! item_vcs {
-    ($vcs:expr, $provider:expr, $opt:expr, $style:expr) => {{
+    ($name:literal, $vcs:expr, $provider:expr, $opt:expr, $style:expr) => {{
         let cloned_opts = Arc::clone(&$opt);
         let vcs = $vcs.clone();
-        let style = $style;
+        let style = $opt
+            .settings
+            .segment_style($name, $opt.theme.as_deref(), $style);
+        let alert =
+            $opt.settings
+                .alert_style($name, $opt.theme.as_deref(), Style::new().bold().yellow());
         tokio::spawn(async move {
             let begin = std::time::Instant::now();
 
-            if let Some((vcs, path)) = vcs {
-                let res = $provider(&vcs, &cloned_opts, &path)
-                    .await
-                    .map(|c| c.with_style(style.0, style.1));
-                return (provider_name(&$provider), begin.elapsed(), res);
+            if cloned_opts.is_enabled($name) {
+                // the repository is looked up within the budget of the segment as well
+                let provider = async {
+                    let (vcs, path) = vcs.await?;
+                    $provider(&vcs, &cloned_opts, &path).await
+                };
+                let (res, timed_out) = within_budget($name, &cloned_opts, provider).await;
+                let res = res.map(|c| {
+                    let info = if c.is_alert() { alert } else { style.1 };
+                    c.with_style(style.0, info)
+                });
+                return (
+                    $name,
+                    provider_name(&$provider),
+                    begin.elapsed(),
+                    res,
+                    timed_out,
+                );
             }
 
-            (provider_name(&$provider), begin.elapsed(), None)
+            (
+                $name,
+                provider_name(&$provider),
+                begin.elapsed(),
+                None,
+                false,
+            )
         })
     }};
 }
 
+/// Run the provider of the segment `name` within its time budget, if any. When the
+/// budget is exceeded the provider is abandoned, and the segment shows the placeholder
+/// (if configured) or is omitted.
+async fn within_budget<F, T>(
+    name: &str,
+    opts: &Options,
+    provider: F,
+) -> (Option<Chunk<SmolStr>>, bool)
+where
+    F: Future<Output = Option<Chunk<T>>>,
+    T: Display,
+{
+    let Some(budget) = opts.segment_timeout(name) else {
+        return (provider.await.map(Chunk::into_smolstr), false);
+    };
+
+    match tokio::time::timeout(budget, provider).await {
+        Ok(res) => (res.map(Chunk::into_smolstr), false),
+        Err(_) => (
+            opts.settings.timeout_placeholder.clone().map(Chunk::info),
+            true,
+        ),
+    }
+}
+
 pub async fn print_prompt(opts: Options) -> anyhow::Result<()> {
-    let start = if opts.timings {
+    let json = opts.format == Some(Format::Json);
+    let start = if opts.timings || json {
         Some(std::time::Instant::now())
     } else {
         None
@@ -80,7 +148,10 @@ pub async fn print_prompt(opts: Options) -> anyhow::Result<()> {
 
     let opts = Arc::new(opts);
     let cwd = env::current_dir()?;
-    let vcs = infer_vcs(cwd, &opts).await;
+    let vcs = {
+        let opts = Arc::clone(&opts);
+        async move { infer_vcs(cwd, &opts).await }.boxed().shared()
+    };
 
     let (color, bold, def) = (
         build_color_style(opts.theme.as_deref()),
@@ -89,43 +160,149 @@ pub async fn print_prompt(opts: Options) -> anyhow::Result<()> {
     );
 
     let async_prompt = hlist![
-        item![user, opts, (color, bold)],
-        item![realname, opts, (color, bold)],
-        item![hostname, opts, (color, bold.dimmed())],
-        item![device_name, opts, (color, bold.dimmed())],
-        item![distro, opts, (color, bold.dimmed())],
-        item![pwd, opts, (color, bold)],
-        item![full_pwd, opts, (color, bold)],
-        item![os_show, opts, (color, bold)],
-        item![virt_show, opts, (bold, bold)],
-        item![memory_show, opts, (bold, bold)],
-        item![huge_pages_show, opts, (bold, bold)],
-        item![ssh_show, opts, (bold, def)],
-        item![netif_show, opts, (bold.dimmed(), def.dimmed())],
-        item![net_namespace, opts, (bold, bold)],
-        item![manifest_show, opts, (color, color.dimmed())],
-        item_vcs![vcs, <Vcs as VcsTrait>::branch, opts, (bold, color.bold())],
-        item_vcs![vcs, <Vcs as VcsTrait>::status, opts, (bold, color)],
-        item_vcs![vcs, <Vcs as VcsTrait>::stash, opts, (bold, def)],
+        item!["user", user, opts, (color, bold)],
+        item!["realname", realname, opts, (color, bold)],
+        item!["hostname", hostname, opts, (color, bold.dimmed())],
+        item!["device_name", device_name, opts, (color, bold.dimmed())],
+        item!["distro", distro, opts, (color, bold.dimmed())],
+        item!["pwd", pwd, opts, (color, bold)],
+        item!["full_pwd", full_pwd, opts, (color, bold)],
+        item!["os", os_show, opts, (color, bold)],
+        item!["virt", virt_show, opts, (bold, bold)],
+        item!["memory", memory_show, opts, (bold, bold)],
+        item!["huge_pages", huge_pages_show, opts, (bold, bold)],
+        item!["ssh", ssh_show, opts, (bold, def)],
+        item!["netif", netif_show, opts, (bold.dimmed(), def.dimmed())],
+        item!["netns", net_namespace, opts, (bold, bold)],
+        item!["manifest", manifest_show, opts, (color, color.dimmed())],
+        item_vcs![
+            "vcs.branch",
+            vcs,
+            <Vcs as VcsTrait>::branch,
+            opts,
+            (bold, color.bold())
+        ],
+        item_vcs![
+            "vcs.remote",
+            vcs,
+            <Vcs as VcsTrait>::remote,
+            opts,
+            (bold, color.dimmed())
+        ],
+        item_vcs![
+            "vcs.operation",
+            vcs,
+            <Vcs as VcsTrait>::operation,
+            opts,
+            (bold, bold.yellow())
+        ],
+        item_vcs![
+            "vcs.status",
+            vcs,
+            <Vcs as VcsTrait>::status,
+            opts,
+            (bold, color)
+        ],
+        item_vcs!["vcs.diff", vcs, <Vcs as VcsTrait>::diff, opts, (bold, def)],
+        item_vcs![
+            "vcs.submodules",
+            vcs,
+            <Vcs as VcsTrait>::submodules,
+            opts,
+            (bold, color.dimmed())
+        ],
+        item_vcs![
+            "vcs.partial",
+            vcs,
+            <Vcs as VcsTrait>::partial,
+            opts,
+            (bold, color.dimmed())
+        ],
+        item_vcs![
+            "vcs.stash",
+            vcs,
+            <Vcs as VcsTrait>::stash,
+            opts,
+            (bold, def)
+        ],
         item_vcs![
+            "vcs.worktree",
             vcs,
             <Vcs as VcsTrait>::worktree,
             opts,
             (bold, bold.dimmed())
         ],
-        item_vcs![vcs, <Vcs as VcsTrait>::commit, opts, (bold, bold)],
-        item_vcs![vcs, <Vcs as VcsTrait>::divergence, opts, (bold, def)],
-        item![duration_show, opts, (def, def.dimmed())],
-        item![exit_code_show, opts, (bold.red(), bold)],
+        item_vcs![
+            "vcs.commit",
+            vcs,
+            <Vcs as VcsTrait>::commit,
+            opts,
+            (bold, bold)
+        ],
+        item_vcs![
+            "vcs.age",
+            vcs,
+            <Vcs as VcsTrait>::age,
+            opts,
+            (def, color.dimmed())
+        ],
+        item_vcs![
+            "vcs.divergence",
+            vcs,
+            <Vcs as VcsTrait>::divergence,
+            opts,
+            (bold, def)
+        ],
+        item_vcs!["vcs.push", vcs, <Vcs as VcsTrait>::push, opts, (bold, def)],
+        item_vcs![
+            "vcs.base",
+            vcs,
+            <Vcs as VcsTrait>::base,
+            opts,
+            (color.dimmed(), def)
+        ],
+        item_vcs![
+            "vcs.fetch",
+            vcs,
+            <Vcs as VcsTrait>::fetch,
+            opts,
+            (def.dimmed(), def.dimmed())
+        ],
+        item!["duration", duration_show, opts, (def, def.dimmed())],
+        item!["clock", clock_show, opts, (def, def.dimmed())],
+        item!["exit_code", exit_code_show, opts, (bold.red(), bold)],
     ];
 
-    let prompt = async_prompt.hjoin().await;
+    let segments: Vec<Segment> = async_prompt.hjoin().await.map(Poly(SegmentMapper)).into();
+    let segments = arrange(segments, &opts.order);
 
-    if let Some(start) = start {
-        prompt.map(Poly(TimingMapper));
+    if let Some(start) = start.filter(|_| json) {
+        print_json(&segments, &opts, start.elapsed())?;
+    } else if let Some(start) = start {
+        segments.iter().for_each(print_timing);
         println!("{:<40} -> {:>15?}", "total time", start.elapsed());
     } else {
-        prompt.map(Poly(PrintMapper));
+        let (right, left): (Vec<_>, Vec<_>) = segments
+            .into_iter()
+            .partition(|s| opts.right.iter().any(|r| s.belongs_to(r)));
+        if opts.rprompt {
+            print!(
+                "{}",
+                non_printing(render_line(&right, opts.shell), opts.shell)
+            );
+        } else {
+            print!("{}", render(&left, &right, &opts));
+        }
+    }
+
+    // once the prompt is out, so that it never waits for the fetch
+    if let Some(Some((vcs, root))) = vcs.peek().filter(|_| opts.auto_fetch.is_some()) {
+        std::io::stdout().flush()?;
+        if let Err(e) = fetch::spawn_fetch(&opts, vcs, root) {
+            if opts.debug {
+                eprintln!("auto-fetch: {e}");
+            }
+        }
     }
 
     Ok(())
@@ -159,37 +336,213 @@ where
     }
 }
 
-struct PrintMapper;
-impl<T> Func<Result<(&'static str, Duration, Option<Chunk<T>>), JoinError>> for PrintMapper
+/// The outcome of a provider, with the type of its info erased.
+struct Segment {
+    name: &'static str,
+    provider: &'static str,
+    elapsed: Duration,
+    chunk: Option<Chunk<SmolStr>>,
+    timed_out: bool,
+}
+
+impl Segment {
+    /// Whether the segment is named `name` or belongs to the group `name` (e.g. `vcs`).
+    #[inline]
+    fn belongs_to(&self, name: &str) -> bool {
+        belongs_to(self.name, name)
+    }
+}
+
+type ProviderOutput<T> = (&'static str, &'static str, Duration, Option<Chunk<T>>, bool);
+
+struct SegmentMapper;
+impl<T> Func<Result<ProviderOutput<T>, JoinError>> for SegmentMapper
 where
     T: Display,
 {
-    type Output = ();
+    type Output = Segment;
 
-    fn call(input: Result<(&'static str, Duration, Option<Chunk<T>>), JoinError>) -> Self::Output {
-        if let (_, _, Some(c)) = input.expect("Task panicked") {
-            print!("{c} ")
+    fn call(input: Result<ProviderOutput<T>, JoinError>) -> Self::Output {
+        let (name, provider, elapsed, chunk, timed_out) = input.expect("Task panicked");
+        Segment {
+            name,
+            provider,
+            elapsed,
+            chunk: chunk.map(|c| c.into_smolstr().sanitize()),
+            timed_out,
         }
     }
 }
 
-struct TimingMapper;
-impl<T> Func<Result<(&'static str, Duration, Option<Chunk<T>>), JoinError>> for TimingMapper
-where
-    T: Display,
-{
-    type Output = ();
+/// Arrange the segments in the requested order: the segments (or groups of segments)
+/// listed in `order` come first, the remaining ones follow in the default order.
+fn arrange(mut segments: Vec<Segment>, order: &[SmolStr]) -> Vec<Segment> {
+    let mut arranged = Vec::with_capacity(segments.len());
+    for name in order {
+        let (selected, rest): (Vec<_>, Vec<_>) =
+            segments.into_iter().partition(|s| s.belongs_to(name));
+        arranged.extend(selected);
+        segments = rest;
+    }
+    arranged.extend(segments);
+    arranged
+}
 
-    fn call(input: Result<(&'static str, Duration, Option<Chunk<T>>), JoinError>) -> Self::Output {
-        let (f, dur, c) = input.expect("Task panicked");
-        let f = f.replace("auraline::providers::", "");
-        if let Some(chunk) = c {
-            println!("{f:<40} -> {dur:>15?} : ({chunk})");
-        } else {
-            println!("{f:<40} -> {dur:>15?} : (_)");
+fn render(segments: &[Segment], right: &[Segment], opts: &Options) -> String {
+    let mut out = String::new();
+    if opts.shell == Some(Shell::Bash) {
+        if let Some(rprompt) = bash_rprompt(right) {
+            out.push_str(&rprompt);
         }
     }
+
+    let prompt = if let Some(template) = &opts.template {
+        template.render(|name| render_group(segments, name, opts.shell))
+    } else {
+        let mut prompt = String::new();
+        for chunk in chunks(segments.iter(), opts.shell) {
+            let _ = write!(prompt, "{chunk} ");
+        }
+
+        // the whole prompt of the shell ends with the prompt character on a new line
+        if opts.shell.is_some() {
+            prompt.push('\n');
+            prompt.push_str(prompt_char());
+            prompt.push(' ');
+        }
+        prompt
+    };
+
+    out.push_str(&non_printing(prompt, opts.shell));
+    out
 }
+
+/// Mark the escape sequences as non-printing characters for the given shell.
+fn non_printing(prompt: String, shell: Option<Shell>) -> String {
+    match shell.and_then(Shell::non_printing) {
+        Some((open, close)) => wrap_escapes(&prompt, open, close),
+        None => prompt,
+    }
+}
+
+/// The chunks of the segments, with the info escaped for the given shell.
+fn chunks<'a>(
+    segments: impl Iterator<Item = &'a Segment> + 'a,
+    shell: Option<Shell>,
+) -> impl Iterator<Item = Chunk<SmolStr>> + 'a {
+    segments
+        .filter_map(|s| s.chunk.as_ref())
+        .map(move |c| c.escape(shell))
+}
+
+/// Render the segments on a single line, separated by a space.
+fn render_line(segments: &[Segment], shell: Option<Shell>) -> String {
+    chunks(segments.iter(), shell).join(" ")
+}
+
+/// Bash has no right prompt: draw it at the right edge of the line (`$COLUMNS` wide),
+/// restoring the cursor position afterwards, as a sequence of non-printing characters.
+fn bash_rprompt(segments: &[Segment]) -> Option<String> {
+    let rprompt = render_line(segments, Some(Shell::Bash));
+    if rprompt.is_empty() {
+        return None;
+    }
+    let columns = env::var("COLUMNS").ok()?.parse::<usize>().ok()?;
+    // the width is that of the prompt once expanded by bash, without the escapes
+    let column = columns.checked_sub(visible_width(&render_line(segments, None)))?;
+    let forward = if column > 0 {
+        format!("\x1b[{column}C")
+    } else {
+        String::new()
+    };
+    Some(format!("\x01\x1b7\r{forward}{rprompt}\x1b8\x02"))
+}
+
+/// Render the segment `name`, or the segments of the group `name` separated by a space.
+fn render_group(segments: &[Segment], name: &str, shell: Option<Shell>) -> Option<String> {
+    let mut chunks = chunks(segments.iter().filter(|s| s.belongs_to(name)), shell).peekable();
+    chunks.peek()?;
+    Some(chunks.join(" "))
+}
+
+#[inline]
+fn prompt_char() -> &'static str {
+    if unsafe { libc::geteuid() } == 0 {
+        "#"
+    } else {
+        "$"
+    }
+}
+
+/// The outcome of the providers, as printed by `--format json`.
+#[derive(Serialize)]
+struct JsonPrompt<'a> {
+    segments: Vec<JsonSegment<'a>>,
+    elapsed_us: u128,
+}
+
+#[derive(Serialize)]
+struct JsonSegment<'a> {
+    name: &'static str,
+    provider: String,
+    region: &'static str,
+    icon: Option<&'a str>,
+    info: Option<&'a str>,
+    link: Option<&'a str>,
+    value: Option<&'a Value>,
+    elapsed_us: u128,
+    empty: bool,
+    timed_out: bool,
+}
+
+/// Print the enabled segments as a JSON object, with the unstyled icon and info.
+fn print_json(segments: &[Segment], opts: &Options, elapsed: Duration) -> anyhow::Result<()> {
+    let segments = segments
+        .iter()
+        .filter(|s| opts.is_enabled(s.name))
+        .map(|s| JsonSegment {
+            name: s.name,
+            provider: short_provider_name(s.provider),
+            region: if opts.right.iter().any(|r| s.belongs_to(r)) {
+                "right"
+            } else {
+                "left"
+            },
+            icon: s.chunk.as_ref().and_then(Chunk::icon_str),
+            info: s.chunk.as_ref().and_then(Chunk::info_str),
+            link: s.chunk.as_ref().and_then(Chunk::link),
+            value: s.chunk.as_ref().and_then(Chunk::value),
+            elapsed_us: s.elapsed.as_micros(),
+            empty: s.chunk.is_none(),
+            timed_out: s.timed_out,
+        })
+        .collect();
+
+    let prompt = JsonPrompt {
+        segments,
+        elapsed_us: elapsed.as_micros(),
+    };
+    println!("{}", serde_json::to_string(&prompt)?);
+    Ok(())
+}
+
+fn print_timing(segment: &Segment) {
+    let f = short_provider_name(segment.provider);
+    let dur = segment.elapsed;
+    if segment.timed_out {
+        println!("{f:<40} -> {dur:>15?} : (timeout)");
+    } else if let Some(chunk) = &segment.chunk {
+        println!("{f:<40} -> {dur:>15?} : ({chunk})");
+    } else {
+        println!("{f:<40} -> {dur:>15?} : (_)");
+    }
+}
+
+#[inline]
+fn short_provider_name(provider: &str) -> String {
+    provider.replace("auraline::providers::", "")
+}
+
 #[inline]
 fn provider_name<T>(_: &T) -> &'static str {
     std::any::type_name::<T>()
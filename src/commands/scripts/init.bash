@@ -1,7 +1,19 @@
 auraline_pre_cmd() {
+   [ "$BASH_COMMAND" = auraline_prompt_command ] && return
    echo -n "$(date +%s%N)" > /tmp/auraline_cmd_start.$$
 }
 
+auraline_prompt_command() {
+   local status=$?
+   PS1="$(COLUMNS=$COLUMNS ~/.cargo/bin/auraline prompt --shell bash --exit-code $status)"
+   return $status
+}
+
 trap 'auraline_pre_cmd' DEBUG
 
-PS1='$(~/.cargo/bin/auraline prompt --exit-code $?)\n\$ '
+# run first, to see the exit code of the last command, keeping the prompt commands already set
+if [[ "$(declare -p PROMPT_COMMAND 2>/dev/null)" == "declare -a"* ]]; then
+   PROMPT_COMMAND=(auraline_prompt_command "${PROMPT_COMMAND[@]}")
+else
+   PROMPT_COMMAND="auraline_prompt_command${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
+fi
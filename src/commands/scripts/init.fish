@@ -0,0 +1,11 @@
+function auraline_preexec --on-event fish_preexec
+    date +%s%N > /tmp/auraline_cmd_start.$fish_pid
+end
+
+function fish_prompt
+    ~/.cargo/bin/auraline prompt --shell fish --exit-code $status
+end
+
+function fish_right_prompt
+    ~/.cargo/bin/auraline prompt --shell fish --rprompt --exit-code $status
+end
@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use frunk::Semigroup;
+use owo_colors::Style;
+use serde::{Deserialize, Deserializer};
+use smol_str::SmolStr;
+
+use crate::options::{belongs_to, parse_duration, parse_segment, GitBackend, Options, SEGMENTS};
+use crate::style::parse_style;
+use crate::template::Template;
+
+const AURALINE_CONFIG: &str = "AURALINE_CONFIG";
+
+/// The content of the `config.toml` file.
+///
+/// ```toml
+/// profile = "lean"
+/// theme = "cyan"
+/// nerd_font = true
+/// hyperlinks = true
+/// disk_cache = true
+/// sysroot = "/var/lib/machines/debian"
+/// git_backend = "native"
+/// base_branch = "origin/main"
+/// lfs = true
+/// commit_summary = true
+/// auto_fetch = "15m"
+/// segments = ["exit_code", "user", "hostname", "vcs", "pwd"]
+/// timeout = "150ms"
+/// timeout_placeholder = "…"
+///
+/// [style.vcs]
+/// icon = "bold"
+/// info = "bold theme"
+///
+/// [duration]
+/// min = "500ms"
+///
+/// [fetch]
+/// min = "1h"
+/// stale = "3d"
+///
+/// [timeouts]
+/// vcs = "300ms"
+///
+/// [exec]
+/// timeout = "1s"
+/// timeouts = { "git status" = "3s" }
+/// env = { GIT_CONFIG_NOSYSTEM = "1" }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub profile: Option<SmolStr>,
+    pub theme: Option<SmolStr>,
+    pub nerd_font: bool,
+    /// Link the segments to their web page.
+    pub hyperlinks: bool,
+    /// Enabled segments, in the order they are printed.
+    pub segments: Vec<SmolStr>,
+    /// Segments of the right prompt.
+    pub right: Vec<SmolStr>,
+    /// The prompt template, see [`Template`].
+    pub template: Option<SmolStr>,
+    /// The time budget of each provider.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub timeout: Option<Duration>,
+    /// Persist the outputs of the VCS commands across the prompts.
+    pub disk_cache: bool,
+    /// The root under which `/proc`, `/sys` and `/etc` are read.
+    pub sysroot: Option<PathBuf>,
+    /// How git repositories are read.
+    pub git_backend: Option<GitBackend>,
+    /// The branch the divergence of `vcs.base` is counted from.
+    pub base_branch: Option<SmolStr>,
+    /// Count the Git LFS files not fetched yet in `vcs.partial`.
+    pub lfs: bool,
+    /// Show the subject and the author of the last commit in `vcs.age`.
+    pub commit_summary: bool,
+    /// Fetch the remotes in the background at most once per this interval.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub auto_fetch: Option<Duration>,
+    pub style: BTreeMap<SmolStr, SegmentStyle>,
+    pub duration: DurationSettings,
+    pub fetch: FetchSettings,
+    /// The time budget of a segment (or of a group of segments).
+    #[serde(deserialize_with = "deserialize_durations")]
+    pub timeouts: BTreeMap<SmolStr, Duration>,
+    /// Shown in place of the segments whose provider timed out (omitted otherwise).
+    pub timeout_placeholder: Option<SmolStr>,
+    pub exec: ExecSettings,
+}
+
+/// Per-segment styles and provider-specific settings.
+#[derive(Debug, Default, Clone)]
+pub struct Settings {
+    pub style: BTreeMap<SmolStr, SegmentStyle>,
+    pub duration: DurationSettings,
+    pub fetch: FetchSettings,
+    /// The time budget of a segment (or of a group of segments).
+    pub timeouts: BTreeMap<SmolStr, Duration>,
+    /// Shown in place of the segments whose provider timed out (omitted otherwise).
+    pub timeout_placeholder: Option<SmolStr>,
+    pub exec: ExecSettings,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SegmentStyle {
+    pub icon: Option<SmolStr>,
+    pub info: Option<SmolStr>,
+    /// The style of the info past a threshold (e.g. the age of a stale fetch).
+    pub alert: Option<SmolStr>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DurationSettings {
+    /// Hide the duration of commands faster than this.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub min: Option<Duration>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FetchSettings {
+    /// Hide the age of the last fetch below this.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub min: Option<Duration>,
+    /// Highlight the age of the last fetch past this (7 days by default).
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub stale: Option<Duration>,
+}
+
+/// How the external commands (`git`, `hg`, `ip`, ...) are run.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecSettings {
+    /// The time a command is given before being killed.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub timeout: Option<Duration>,
+    /// The timeout of a command (e.g. `git`) or of a subcommand (e.g. `git status`).
+    #[serde(deserialize_with = "deserialize_durations")]
+    pub timeouts: BTreeMap<SmolStr, Duration>,
+    /// Environment variables set for the commands.
+    pub env: BTreeMap<SmolStr, SmolStr>,
+}
+
+impl Config {
+    /// Load the configuration from `$AURALINE_CONFIG`, or from `auraline/config.toml`
+    /// in the user config directory. A missing file is not an error.
+    pub fn load() -> anyhow::Result<Option<Self>> {
+        let (path, explicit) = match std::env::var_os(AURALINE_CONFIG) {
+            Some(path) => (PathBuf::from(path), true),
+            None => match default_config_path() {
+                Some(path) => (path, false),
+                None => return Ok(None),
+            },
+        };
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("cannot read '{}'", path.display())),
+        };
+
+        toml::from_str(&content)
+            .map(Some)
+            .with_context(|| format!("config parse error in '{}'", path.display()))
+    }
+
+    /// Turn the configuration into prompt options, to be combined with lower precedence
+    /// than the command line, `AURALINE_OPTIONS` and the selected profile.
+    pub fn to_options(&self) -> anyhow::Result<Options> {
+        let mut opts = Options {
+            theme: self.theme.clone(),
+            nerd_font: self.nerd_font,
+            hyperlinks: self.hyperlinks,
+            profile: self.profile.clone(),
+            order: self.segments.clone(),
+            right: self.right.clone(),
+            timeout: self.timeout,
+            disk_cache: self.disk_cache,
+            sysroot: self.sysroot.clone(),
+            git_backend: self.git_backend,
+            base_branch: self.base_branch.clone(),
+            lfs: self.lfs,
+            commit_summary: self.commit_summary,
+            auto_fetch: self.auto_fetch,
+            template: self
+                .template
+                .as_deref()
+                .map(str::parse::<Template>)
+                .transpose()
+                .map_err(anyhow::Error::msg)
+                .context("config template error")?,
+            settings: Settings {
+                style: self.style.clone(),
+                duration: self.duration.clone(),
+                fetch: self.fetch.clone(),
+                timeouts: self.timeouts.clone(),
+                timeout_placeholder: self.timeout_placeholder.clone(),
+                exec: self.exec.clone(),
+            },
+            ..Default::default()
+        };
+
+        let listed: Vec<_> = self.segments.iter().chain(&self.right).collect();
+        for segment in &listed {
+            opts.enable(segment)?;
+        }
+
+        // listing some segments of a group (e.g. `vcs.branch`) selects them among the group
+        for segment in SEGMENTS {
+            if let Some((group, _)) = segment.split_once('.') {
+                if listed.iter().any(|s| belongs_to(s, group))
+                    && !listed.iter().any(|s| belongs_to(segment, s))
+                {
+                    opts.disable(segment)?;
+                }
+            }
+        }
+
+        for segment in self.timeouts.keys() {
+            parse_segment(segment).map_err(anyhow::Error::msg)?;
+        }
+
+        Ok(opts)
+    }
+
+    /// Disable the segments of `opts` (e.g. those of a profile) not listed in `segments`
+    /// nor in `right`, when the configuration lists any.
+    pub fn select_segments(&self, opts: &mut Options) -> anyhow::Result<()> {
+        if self.segments.is_empty() {
+            return Ok(());
+        }
+
+        let listed = |segment: &str| {
+            self.segments
+                .iter()
+                .chain(&self.right)
+                .any(|s| belongs_to(segment, s))
+        };
+        for segment in SEGMENTS.iter().filter(|s| !listed(s)) {
+            opts.disable(segment)?;
+        }
+        Ok(())
+    }
+}
+
+impl Settings {
+    pub const fn new() -> Self {
+        Self {
+            style: BTreeMap::new(),
+            duration: DurationSettings { min: None },
+            fetch: FetchSettings {
+                min: None,
+                stale: None,
+            },
+            timeouts: BTreeMap::new(),
+            timeout_placeholder: None,
+            exec: ExecSettings {
+                timeout: None,
+                timeouts: BTreeMap::new(),
+                env: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Return the time budget of the given segment, falling back to that of its group.
+    pub fn segment_timeout(&self, segment: &str) -> Option<Duration> {
+        let group = segment.split_once('.').map(|(group, _)| group);
+        self.timeouts
+            .get(segment)
+            .or_else(|| group.and_then(|g| self.timeouts.get(g)))
+            .copied()
+    }
+
+    /// Return the (icon, info) style of the given segment, falling back to the style
+    /// of its group (e.g. `vcs` for `vcs.branch`) and then to the `default` one.
+    pub fn segment_style(
+        &self,
+        segment: &str,
+        theme: Option<&str>,
+        default: (Style, Style),
+    ) -> (Style, Style) {
+        (
+            self.lookup_style(segment, theme, |s| s.icon.as_ref())
+                .unwrap_or(default.0),
+            self.lookup_style(segment, theme, |s| s.info.as_ref())
+                .unwrap_or(default.1),
+        )
+    }
+
+    /// Return the style of the info of the given segment past its threshold, falling back
+    /// to that of its group and then to the `default` one.
+    pub fn alert_style(&self, segment: &str, theme: Option<&str>, default: Style) -> Style {
+        self.lookup_style(segment, theme, |s| s.alert.as_ref())
+            .unwrap_or(default)
+    }
+
+    fn lookup_style(
+        &self,
+        segment: &str,
+        theme: Option<&str>,
+        f: fn(&SegmentStyle) -> Option<&SmolStr>,
+    ) -> Option<Style> {
+        let group = segment.split_once('.').map(|(group, _)| group);
+        self.style
+            .get(segment)
+            .and_then(f)
+            .or_else(|| group.and_then(|g| self.style.get(g)).and_then(f))
+            .map(|spec| parse_style(spec, theme))
+    }
+}
+
+impl Semigroup for Settings {
+    fn combine(&self, other: &Self) -> Self {
+        let mut style = other.style.clone();
+        style.extend(self.style.clone());
+        let mut timeouts = other.timeouts.clone();
+        timeouts.extend(self.timeouts.clone());
+        let mut exec_timeouts = other.exec.timeouts.clone();
+        exec_timeouts.extend(self.exec.timeouts.clone());
+        let mut env = other.exec.env.clone();
+        env.extend(self.exec.env.clone());
+        Self {
+            style,
+            duration: DurationSettings {
+                min: self.duration.min.or(other.duration.min),
+            },
+            fetch: FetchSettings {
+                min: self.fetch.min.or(other.fetch.min),
+                stale: self.fetch.stale.or(other.fetch.stale),
+            },
+            timeouts,
+            timeout_placeholder: self
+                .timeout_placeholder
+                .clone()
+                .or(other.timeout_placeholder.clone()),
+            exec: ExecSettings {
+                timeout: self.exec.timeout.or(other.exec.timeout),
+                timeouts: exec_timeouts,
+                env,
+            },
+        }
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::home_dir().map(|home| home.join(".config")))
+        .map(|dir| dir.join("auraline").join("config.toml"))
+}
+
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<SmolStr>::deserialize(deserializer)?
+        .map(|s| parse_duration(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_durations<'de, D>(deserializer: D) -> Result<BTreeMap<SmolStr, Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    BTreeMap::<SmolStr, SmolStr>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(k, v)| Ok((k, parse_duration(&v).map_err(serde::de::Error::custom)?)))
+        .collect()
+}
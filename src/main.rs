@@ -2,11 +2,15 @@
 mod chunk;
 mod cmd;
 mod commands;
+mod config;
 mod options;
 mod providers;
 mod style;
+mod template;
+mod value;
 
-use crate::options::{Cli, Options};
+use crate::config::Config;
+use crate::options::{Cli, Options, SEGMENTS};
 
 use anyhow::Context;
 use clap::Parser;
@@ -21,11 +25,37 @@ async fn main() -> anyhow::Result<()> {
             commands::init::print_init(options);
         }
 
+        options::Commands::Daemon(options) => {
+            commands::daemon::run_daemon(options).await?;
+        }
+
+        options::Commands::Fetch(options) => {
+            commands::fetch::run_fetch(options).await?;
+        }
+
         options::Commands::Prompt(mut options) => {
-            // combine profile options (either specified by command line or env variable)
-            if let Ok(profile_name) = std::env::var("AURALINE_PROFILE") {
-                let profile_opts = commands::profile::get_profile(&profile_name)
+            // Options are combined in order of precedence: command line, profile,
+            // AURALINE_OPTIONS, AURALINE_THEME, AURALINE_SYSROOT and finally the config file.
+            // The segments listed in the config file replace those of the profile.
+            let config = Config::load()?;
+            let command_line = SEGMENTS
+                .iter()
+                .filter(|segment| options.is_enabled(segment))
+                .collect::<Vec<_>>();
+
+            // combine profile options (either specified by command line, env variable or config file)
+            let profile_name = options
+                .profile
+                .clone()
+                .or_else(|| std::env::var("AURALINE_PROFILE").ok().map(Into::into))
+                .or_else(|| config.as_ref().and_then(|c| c.profile.clone()));
+            if let Some(profile_name) = profile_name {
+                let mut profile_opts = commands::profile::get_profile(&profile_name)
                     .with_context(|| format!("profile '{profile_name}' not found"))?;
+                // the segments listed in the config file replace those of the profile
+                if let Some(config) = &config {
+                    config.select_segments(&mut profile_opts)?;
+                }
                 options = options.combine(&profile_opts);
             }
 
@@ -46,6 +76,48 @@ async fn main() -> anyhow::Result<()> {
                 options.theme = options.theme.or(Some(theme.to_smolstr()));
             }
 
+            // Combine with the sysroot specified by AURALINE_SYSROOT environment variable
+            if let Some(sysroot) = std::env::var_os("AURALINE_SYSROOT") {
+                options.sysroot = options.sysroot.or(Some(sysroot.into()));
+            }
+
+            // Combine with the segments, styles and settings of the config file
+            if let Some(config) = config {
+                options = options.combine(&config.to_options()?);
+            }
+
+            // and the segments enabled on the command line win over the selection of a
+            // group by the config file (e.g. `--vcs` over `segments = ["vcs.branch"]`)
+            for segment in command_line {
+                options.enable(segment)?;
+            }
+
+            // Enable the segments used by the template
+            if let Some(template) = options.template.clone() {
+                for segment in template.segments() {
+                    options.enable(segment)?;
+                }
+            }
+
+            options.select_region()?;
+
+            cmd::CMD.use_policy(cmd::ExecPolicy::from_settings(
+                &options.settings.exec,
+                options.debug,
+            ));
+
+            if options.disk_cache {
+                if let Some(disk) = cmd::DiskCache::new() {
+                    cmd::CMD.use_disk_cache(disk);
+                }
+            }
+
+            if options.client {
+                if let Some(socket) = commands::daemon::find_socket() {
+                    cmd::CMD.use_daemon(socket);
+                }
+            }
+
             commands::prompt::print_prompt(options).await?;
         }
     }
@@ -1,6 +1,13 @@
-use clap::{ArgAction, Args, Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
 use frunk::Semigroup;
-use smol_str::SmolStr;
+use smol_str::{SmolStr, SmolStrBuilder};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::config::Settings;
+use crate::template::Template;
 
 #[derive(Parser, Debug, Default)]
 #[command(author, version, about, long_about = None, disable_help_flag = true, disable_help_subcommand = true)]
@@ -13,9 +20,13 @@ pub struct Cli {
 }
 
 #[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum Commands {
     Prompt(Options),
     Init(InitOptions),
+    Daemon(DaemonOptions),
+    #[command(hide = true)]
+    Fetch(FetchOptions),
 }
 
 impl Default for Commands {
@@ -29,6 +40,26 @@ pub struct InitOptions {
     pub shell: SmolStr,
 }
 
+#[derive(Args, Debug, Default)]
+pub struct DaemonOptions {
+    #[clap(
+        long,
+        value_parser = parse_duration,
+        help = "Specify how long the outputs of the commands are reused [default: 5s]"
+    )]
+    pub ttl: Option<Duration>,
+}
+
+/// The options of the background fetch spawned by `--auto-fetch`.
+#[derive(Args, Debug, Default)]
+pub struct FetchOptions {
+    #[clap(long, help = "The lock file held while fetching")]
+    pub lock: PathBuf,
+
+    #[clap(last = true, required = true, help = "The fetch command")]
+    pub command: Vec<String>,
+}
+
 #[derive(Args, Debug, Default)]
 pub struct Options {
     #[clap(short('u'), long, help = "Basic user info")]
@@ -86,23 +117,188 @@ pub struct Options {
     #[clap(short('e'), long, help = "Show the duration of the last command)")]
     pub duration: bool,
 
+    #[clap(short('c'), long, help = "Show the current time")]
+    pub clock: bool,
+
     #[clap(long, help = "Specify the exit-code of the last command to show")]
     pub exit_code: Option<u8>,
 
     #[clap(long, help = "Enable timings mode (dev)")]
     pub timings: bool,
 
+    #[clap(long, help = "Report the failures of the commands on stderr (dev)")]
+    pub debug: bool,
+
     #[clap(long, help = "Specify the theme color")]
     pub theme: Option<SmolStr>,
 
     #[clap(long, help = "Use Nerd Fonts")]
     pub nerd_font: bool,
 
+    #[clap(long, help = "Link the segments to their web page (OSC 8)")]
+    pub hyperlinks: bool,
+
     #[clap(long, help = "Specify the prompt profile to use: minimal, lean, nerdy")]
     pub profile: Option<SmolStr>,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_segment,
+        help = "Specify the order of the segments (e.g. vcs,pwd,exit_code)"
+    )]
+    pub order: Vec<SmolStr>,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_segment,
+        help = "Specify the segments of the right prompt (e.g. duration,exit_code)"
+    )]
+    pub right: Vec<SmolStr>,
+
+    #[clap(long, help = "Render the right prompt")]
+    pub rprompt: bool,
+
+    #[clap(
+        long,
+        value_parser = Template::from_str,
+        help = "Specify the prompt template (e.g. \"{user} {pwd}[ {vcs.branch}]\\n❯ \")"
+    )]
+    pub template: Option<Template>,
+
+    #[clap(long, help = "Render the whole prompt of the given shell")]
+    pub shell: Option<Shell>,
+
+    #[clap(long, help = "Specify the output format [default: text]")]
+    pub format: Option<Format>,
+
+    #[clap(
+        long,
+        value_parser = parse_duration,
+        help = "Specify the time budget of each provider (e.g. 150ms)"
+    )]
+    pub timeout: Option<Duration>,
+
+    #[clap(
+        long,
+        help = "Ask the outputs of the commands to the daemon, if running"
+    )]
+    pub client: bool,
+
+    #[clap(
+        long,
+        help = "Persist the outputs of the VCS commands across the prompts"
+    )]
+    pub disk_cache: bool,
+
+    #[clap(
+        long,
+        help = "Read /proc, /sys and /etc under the given root (e.g. a container rootfs)"
+    )]
+    pub sysroot: Option<PathBuf>,
+
+    #[clap(long, help = "Specify how git repositories are read [default: native]")]
+    pub git_backend: Option<GitBackend>,
+
+    #[clap(
+        long,
+        help = "Specify the branch vcs.base counts the divergence from (e.g. origin/main)"
+    )]
+    pub base_branch: Option<SmolStr>,
+
+    #[clap(long, help = "Count the Git LFS files not fetched yet in vcs.partial")]
+    pub lfs: bool,
+
+    #[clap(
+        long,
+        help = "Show the subject and the author of the last commit in vcs.age"
+    )]
+    pub commit_summary: bool,
+
+    #[clap(
+        long,
+        value_parser = parse_duration,
+        help = "Fetch the remotes in the background at most once per interval (e.g. 15m)"
+    )]
+    pub auto_fetch: Option<Duration>,
+
+    /// The segments of a group (e.g. `vcs.status`) enabled or disabled on their own,
+    /// overriding the group.
+    #[clap(skip)]
+    pub subsegments: BTreeMap<&'static str, bool>,
+
+    #[clap(skip)]
+    pub settings: Settings,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Plain,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The styled prompt.
+    Text,
+    /// The outcome of every enabled provider, as a JSON object.
+    Json,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GitBackend {
+    Native,
+    Cli,
+}
+
+impl Shell {
+    /// The markers delimiting the non-printing characters in the prompt, if needed.
+    pub fn non_printing(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Shell::Bash => Some(("\x01", "\x02")),
+            Shell::Zsh => Some(("%{", "%}")),
+            Shell::Fish | Shell::Plain => None,
+        }
+    }
+
+    /// Escape the characters of `s` that the shell would expand in the prompt: `\`, `$`
+    /// and `` ` `` in bash (`PS1` is decoded and then expanded), `%` in zsh.
+    pub fn escape(self, s: &str) -> SmolStr {
+        let escape: fn(char) -> Option<&'static str> = match self {
+            Shell::Bash => |c| match c {
+                '\\' => Some(r"\\\\"),
+                '$' => Some(r"\\$"),
+                '`' => Some(r"\\`"),
+                _ => None,
+            },
+            Shell::Zsh => |c| (c == '%').then_some("%%"),
+            Shell::Fish | Shell::Plain => return SmolStr::new(s),
+        };
+
+        let mut builder = SmolStrBuilder::new();
+        for c in s.chars() {
+            match escape(c) {
+                Some(escaped) => builder.push_str(escaped),
+                None => builder.push(c),
+            }
+        }
+        builder.finish()
+    }
 }
 
 impl Options {
+    /// The path of a system file (e.g. `/etc/os-release`) under the sysroot, if any.
+    pub fn sys_path(&self, path: &str) -> PathBuf {
+        match &self.sysroot {
+            Some(root) => root.join(path.trim_start_matches('/')),
+            None => PathBuf::from(path),
+        }
+    }
+
     pub fn select_str<'a>(&self, normal: &'a str, nerd: &'a str) -> &'a str {
         if self.nerd_font {
             nerd
@@ -110,6 +306,213 @@ impl Options {
             normal
         }
     }
+
+    /// Enable the segment with the given name (e.g. `pwd`, `vcs` or `vcs.branch`).
+    pub fn enable(&mut self, segment: &str) -> anyhow::Result<()> {
+        self.toggle(segment, true)
+    }
+
+    /// Disable the segment with the given name (e.g. `pwd`, `vcs` or `vcs.branch`).
+    pub fn disable(&mut self, segment: &str) -> anyhow::Result<()> {
+        self.toggle(segment, false)
+    }
+
+    /// The time budget of the segment with the given name: its own, that of its group
+    /// or the global one.
+    pub fn segment_timeout(&self, segment: &str) -> Option<Duration> {
+        self.settings.segment_timeout(segment).or(self.timeout)
+    }
+
+    /// Whether the segment with the given name is enabled. A group (e.g. `vcs`) is
+    /// enabled when any of its segments is.
+    pub fn is_enabled(&self, segment: &str) -> bool {
+        if let Some(on) = self.subsegments.get(segment) {
+            return *on;
+        }
+
+        match segment {
+            "user" => self.user,
+            "realname" => self.realname,
+            "hostname" => self.hostname,
+            "device_name" => self.device_name,
+            "distro" => self.distro,
+            "pwd" => self.pwd,
+            "full_pwd" => self.full_pwd,
+            "vcs" => {
+                self.vcs
+                    || self
+                        .subsegments
+                        .iter()
+                        .any(|(s, on)| *on && belongs_to(s, "vcs"))
+            }
+            segment if belongs_to(segment, "vcs") && SEGMENTS.contains(&segment) => {
+                self.vcs && !OPT_IN.contains(&segment)
+            }
+            "ssh" => self.ssh,
+            "os" => self.os,
+            "virt" => self.virt,
+            "netif" => self.netif,
+            "netns" => self.netns,
+            "memory" => self.memory,
+            "huge_pages" => self.huge_pages,
+            "manifest" => self.manifest,
+            "duration" => self.duration,
+            "clock" => self.clock,
+            "exit_code" => self.exit_code.is_some(),
+            _ => false,
+        }
+    }
+
+    fn toggle(&mut self, segment: &str, on: bool) -> anyhow::Result<()> {
+        match parse_segment(segment).map_err(anyhow::Error::msg)?.as_str() {
+            "user" => self.user = on,
+            "realname" => self.realname = on,
+            "hostname" => self.hostname = on,
+            "device_name" => self.device_name = on,
+            "distro" => self.distro = on,
+            "pwd" => self.pwd = on,
+            "full_pwd" => self.full_pwd = on,
+            "vcs" => {
+                self.vcs = on;
+                // a disabled group takes its segments along
+                if !on {
+                    self.subsegments.retain(|s, _| !belongs_to(s, "vcs"));
+                }
+            }
+            // the segments of the group, validated by parse_segment
+            name if belongs_to(name, "vcs") => {
+                let name = SEGMENTS
+                    .iter()
+                    .find(|s| **s == name)
+                    .expect("known segment");
+                self.subsegments.insert(name, on);
+            }
+            "ssh" => self.ssh = on,
+            "os" => self.os = on,
+            "virt" => self.virt = on,
+            "netif" => self.netif = on,
+            "netns" => self.netns = on,
+            "memory" => self.memory = on,
+            "huge_pages" => self.huge_pages = on,
+            "manifest" => self.manifest = on,
+            "duration" => self.duration = on,
+            "clock" => self.clock = on,
+            // the exit code is shown whenever it is passed by the shell
+            "exit_code" if on => {}
+            "exit_code" => self.exit_code = None,
+            _ => unreachable!("segment validated by parse_segment"),
+        }
+        Ok(())
+    }
+
+    /// Keep enabled only the segments of the region being rendered: the `right` ones
+    /// with `--rprompt`, the others otherwise. Bash has no right prompt, and the JSON
+    /// output reports the region of each segment, so both regions are rendered at once.
+    pub fn select_region(&mut self) -> anyhow::Result<()> {
+        let right = std::mem::take(&mut self.right);
+        if self.rprompt {
+            for segment in SEGMENTS {
+                if !right.iter().any(|r| belongs_to(segment, r)) {
+                    self.disable(segment)?;
+                }
+            }
+        }
+
+        for segment in &right {
+            if self.rprompt || self.shell == Some(Shell::Bash) || self.format == Some(Format::Json)
+            {
+                self.enable(segment)?;
+            } else {
+                self.disable(segment)?;
+            }
+        }
+
+        self.right = right;
+        Ok(())
+    }
+}
+
+/// Whether the segment `segment` is named `name` or belongs to the group `name`
+/// (e.g. `vcs.branch` belongs to `vcs`).
+pub fn belongs_to(segment: &str, name: &str) -> bool {
+    segment == name
+        || segment
+            .strip_prefix(name)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// The segments of a group that are shown only when enabled on their own (e.g. listed in
+/// the `segments` of the config file), not along with the group. Among [`SEGMENTS`].
+const OPT_IN: &[&str] = &["vcs.age", "vcs.diff", "vcs.fetch", "vcs.push", "vcs.remote"];
+
+/// The names of the segments of the prompt, in the default order.
+pub const SEGMENTS: &[&str] = &[
+    "user",
+    "realname",
+    "hostname",
+    "device_name",
+    "distro",
+    "pwd",
+    "full_pwd",
+    "os",
+    "virt",
+    "memory",
+    "huge_pages",
+    "ssh",
+    "netif",
+    "netns",
+    "manifest",
+    "vcs.branch",
+    "vcs.remote",
+    "vcs.operation",
+    "vcs.status",
+    "vcs.diff",
+    "vcs.submodules",
+    "vcs.partial",
+    "vcs.stash",
+    "vcs.worktree",
+    "vcs.commit",
+    "vcs.age",
+    "vcs.divergence",
+    "vcs.push",
+    "vcs.base",
+    "vcs.fetch",
+    "duration",
+    "clock",
+    "exit_code",
+];
+
+/// Parse the name of a segment, or of a group of segments (e.g. `vcs`).
+pub fn parse_segment(s: &str) -> Result<SmolStr, String> {
+    let s = s.trim();
+    SEGMENTS
+        .iter()
+        .any(|seg| belongs_to(seg, s))
+        .then(|| SmolStr::new(s))
+        .ok_or_else(|| format!("unknown segment '{s}'"))
+}
+
+/// Parse a duration such as `150ms`, `2s`, `1.5m` or `1h` (a bare number is in seconds).
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value = value
+        .parse::<f64>()
+        .map_err(|_| format!("invalid duration '{s}'"))?;
+    let secs = match unit.trim() {
+        "ns" => value / 1_000_000_000.0,
+        "us" | "μs" => value / 1_000_000.0,
+        "ms" => value / 1_000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        "d" => value * 86400.0,
+        _ => return Err(format!("invalid duration unit in '{s}'")),
+    };
+    Duration::try_from_secs_f64(secs).map_err(|e| format!("invalid duration '{s}': {e}"))
 }
 
 impl Semigroup for Options {
@@ -132,11 +535,44 @@ impl Semigroup for Options {
             huge_pages: self.huge_pages || other.huge_pages,
             manifest: self.manifest || other.manifest,
             duration: self.duration || other.duration,
+            clock: self.clock || other.clock,
             exit_code: self.exit_code.or(other.exit_code),
             timings: self.timings || other.timings,
+            debug: self.debug || other.debug,
             theme: self.theme.clone().or(other.theme.clone()),
             nerd_font: self.nerd_font || other.nerd_font,
+            hyperlinks: self.hyperlinks || other.hyperlinks,
             profile: self.profile.clone().or(other.profile.clone()),
+            order: if self.order.is_empty() {
+                other.order.clone()
+            } else {
+                self.order.clone()
+            },
+            right: if self.right.is_empty() {
+                other.right.clone()
+            } else {
+                self.right.clone()
+            },
+            rprompt: self.rprompt || other.rprompt,
+            template: self.template.clone().or(other.template.clone()),
+            shell: self.shell.or(other.shell),
+            format: self.format.or(other.format),
+            git_backend: self.git_backend.or(other.git_backend),
+            base_branch: self.base_branch.clone().or(other.base_branch.clone()),
+            lfs: self.lfs || other.lfs,
+            commit_summary: self.commit_summary || other.commit_summary,
+            auto_fetch: self.auto_fetch.or(other.auto_fetch),
+            timeout: self.timeout.or(other.timeout),
+            client: self.client || other.client,
+            disk_cache: self.disk_cache || other.disk_cache,
+            sysroot: self.sysroot.clone().or(other.sysroot.clone()),
+            subsegments: other
+                .subsegments
+                .iter()
+                .chain(&self.subsegments)
+                .map(|(s, on)| (*s, *on))
+                .collect(),
+            settings: self.settings.combine(&other.settings),
         }
     }
 }
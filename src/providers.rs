@@ -1,4 +1,5 @@
 pub mod basic;
+pub mod clock;
 pub mod duration;
 pub mod exit_code;
 pub mod huge_pages;
@@ -1,6 +1,7 @@
 use crate::{
     chunk::{Adjoin, Chunk},
     options::Options,
+    value::Value,
 };
 
 #[inline]
@@ -70,7 +71,7 @@ pub async fn pwd(opts: &Options) -> Option<Chunk<std::borrow::Cow<'static, str>>
                     std::borrow::Cow::Owned(p.to_string_lossy().into_owned())
                 };
 
-                Chunk::info(pwd)
+                Chunk::info(pwd).with_value(Value::Path(p))
             })
         })
         .flatten()
@@ -80,9 +81,9 @@ pub async fn pwd(opts: &Options) -> Option<Chunk<std::borrow::Cow<'static, str>>
 pub async fn full_pwd(opts: &Options) -> Option<Chunk<String>> {
     opts.full_pwd
         .then(|| {
-            std::env::current_dir()
-                .ok()
-                .map(|pwd| Chunk::info(pwd.to_string_lossy().into_owned()))
+            std::env::current_dir().ok().map(|pwd| {
+                Chunk::info(pwd.to_string_lossy().into_owned()).with_value(Value::Path(pwd))
+            })
         })
         .flatten()
 }
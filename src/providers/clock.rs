@@ -0,0 +1,19 @@
+use crate::{chunk::Chunk, options::Options};
+use smol_str::{format_smolstr, SmolStr};
+
+pub async fn show(opts: &Options) -> Option<Chunk<SmolStr>> {
+    if !opts.clock {
+        return None;
+    }
+
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return None;
+    }
+
+    Some(Chunk::new(
+        opts.select_str("◷", "\u{f017}"),
+        format_smolstr!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec),
+    ))
+}
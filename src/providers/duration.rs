@@ -1,4 +1,4 @@
-use crate::{chunk::Chunk, options::Options};
+use crate::{chunk::Chunk, options::Options, value::Value};
 use scopeguard::defer;
 use smol_str::{format_smolstr, SmolStr};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -22,7 +22,10 @@ pub async fn show(opts: &Options) -> Option<Chunk<SmolStr>> {
     let start_nanos = start_time_str.parse::<u128>().ok()?;
     if end_nanos > start_nanos {
         let duration = Duration::from_nanos((end_nanos - start_nanos) as u64);
-        return Some(format_duration(duration));
+        if opts.settings.duration.min.is_some_and(|min| duration < min) {
+            return None;
+        }
+        return Some(format_duration(duration).with_value(Value::Duration(duration)));
     }
 
     None
@@ -1,4 +1,4 @@
-use crate::{chunk::Chunk, options::Options, style::to_superscript};
+use crate::{chunk::Chunk, options::Options, style::to_superscript, value::Value};
 use smol_str::SmolStr;
 
 pub async fn show(opts: &Options) -> Option<Chunk<SmolStr>> {
@@ -7,7 +7,9 @@ pub async fn show(opts: &Options) -> Option<Chunk<SmolStr>> {
         None
     } else {
         let mut buffer = itoa::Buffer::new();
-        let exit_code = buffer.format(exit_code);
-        Some(Chunk::new("✘", to_superscript(exit_code)))
+        Some(
+            Chunk::new("✘", to_superscript(buffer.format(exit_code)))
+                .with_value(Value::Integer(exit_code.into())),
+        )
     }
 }
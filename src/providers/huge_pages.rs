@@ -9,9 +9,13 @@ pub async fn show(opts: &Options) -> Option<Chunk<SmolStr>> {
         return None;
     }
     let mut builder = SmolStrBuilder::new();
-    let huge_pages = get_hugepages_status().await;
+    let huge_pages = get_hugepages_status(opts).await;
     if let Some(huge_pages) = huge_pages {
-        for hp in huge_pages {
+        for (n, hp) in huge_pages.iter().enumerate() {
+            // the pages of the NUMA nodes are separated by `|`
+            if n > 0 {
+                builder.push('|');
+            }
             builder.push_str(
                 &hp.pages
                     .iter()
@@ -36,7 +40,6 @@ pub struct HugePageInfo {
 /// Represents all huge page configurations for a single NUMA node.
 /// If `node` is None, it represents a non-NUMA system.
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct HugePage {
     pub node: Option<u32>,
     pub pages: SmallVec<[HugePageInfo; 4]>,
@@ -50,8 +53,7 @@ async fn process_hugepage_dir(path: &Path, node: Option<u32>) -> Option<HugePage
 
     // Read the directory content, continue only if successful.
     if let Ok(mut entries) = fs::read_dir(path).await {
-        let mut tmp = entries.next_entry().await.ok()?.into_iter();
-        for entry in &mut tmp {
+        while let Some(entry) = entries.next_entry().await.ok()? {
             let file_name = entry.file_name();
             let file_name_str = match file_name.to_str() {
                 Some(s) => s,
@@ -84,6 +86,7 @@ async fn process_hugepage_dir(path: &Path, node: Option<u32>) -> Option<HugePage
     }
 
     if !pages_info.is_empty() {
+        pages_info.sort_by_key(|page| page.size_kb);
         Some(HugePage {
             node,
             pages: pages_info,
@@ -98,16 +101,18 @@ async fn process_hugepage_dir(path: &Path, node: Option<u32>) -> Option<HugePage
 /// It checks for NUMA support and scans either per-node directories or the
 /// global hugepages directory.
 /// Returns `Some(Vec<HugePage>)` if configurations are found, otherwise `None`.
-pub async fn get_hugepages_status() -> Option<SmallVec<[HugePage; 4]>> {
+/// The directories are looked up under the sysroot, if any.
+pub async fn get_hugepages_status(opts: &Options) -> Option<SmallVec<[HugePage; 4]>> {
     const NUMA_NODE_BASE_DIR: &str = "/sys/devices/system/node";
     const NO_NUMA_HUGE_DIR: &str = "/sys/kernel/mm/hugepages";
 
     let mut hugepages = SmallVec::<[_; 4]>::new();
+    let numa_node_base_dir = opts.sys_path(NUMA_NODE_BASE_DIR);
 
     // Check if the system supports NUMA by checking for the base directory's existence.
-    if Path::new(NUMA_NODE_BASE_DIR).exists() {
+    if numa_node_base_dir.exists() {
         // NUMA system: iterate through node directories (e.g., node0, node1, ...).
-        if let Ok(mut entries) = fs::read_dir(NUMA_NODE_BASE_DIR).await {
+        if let Ok(mut entries) = fs::read_dir(&numa_node_base_dir).await {
             while let Some(entry) = entries.next_entry().await.ok()? {
                 let file_name = entry.file_name();
                 let file_name_str = match file_name.to_str() {
@@ -127,10 +132,11 @@ pub async fn get_hugepages_status() -> Option<SmallVec<[HugePage; 4]>> {
                 }
             }
         }
+        hugepages.sort_by_key(|hp| hp.node);
     } else {
         // Non-NUMA system: check the single global directory.
-        let path = Path::new(NO_NUMA_HUGE_DIR);
-        if let Some(hp) = process_hugepage_dir(path, None).await {
+        let path = opts.sys_path(NO_NUMA_HUGE_DIR);
+        if let Some(hp) = process_hugepage_dir(&path, None).await {
             hugepages.push(hp);
         }
     }
@@ -425,6 +425,7 @@ fn version_strategy(lang: Language, key: &str) -> Option<VersionStrategy> {
 
 /// Apply `strategy` to the raw text of a manifest file and return the version string,
 /// or `None` if the pattern is not found or the matched value is empty.
+#[allow(clippy::manual_strip, clippy::manual_pattern_char_comparison)]
 fn extract_version(strategy: VersionStrategy, content: &str) -> Option<SmolStr> {
     match strategy {
         VersionStrategy::KeyValueQuoted(key, sep) => {
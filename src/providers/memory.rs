@@ -1,4 +1,4 @@
-use crate::{chunk::Chunk, options::Options};
+use crate::{chunk::Chunk, options::Options, value::Value};
 use smol_str::{format_smolstr, SmolStr};
 use sysinfo::{MemoryRefreshKind, RefreshKind};
 
@@ -6,12 +6,44 @@ pub async fn show(opts: &Options) -> Option<Chunk<SmolStr>> {
     if !opts.memory {
         return None;
     }
-    let info = sysinfo::System::new_with_specifics(
-        RefreshKind::nothing().with_memory(MemoryRefreshKind::everything()),
-    );
-    let mem_perc = info.used_memory() as f64 / info.total_memory() as f64 * 100.0;
-    Some(Chunk::new(
-        opts.select_str("μ", ""),
-        format_smolstr!("{:.1}%", mem_perc),
-    ))
+    let mem_perc = match &opts.sysroot {
+        Some(_) => meminfo_usage(opts).await?,
+        None => {
+            let info = sysinfo::System::new_with_specifics(
+                RefreshKind::nothing().with_memory(MemoryRefreshKind::everything()),
+            );
+            info.used_memory() as f64 / info.total_memory() as f64 * 100.0
+        }
+    };
+    Some(
+        Chunk::new(
+            opts.select_str("μ", ""),
+            format_smolstr!("{:.1}%", mem_perc),
+        )
+        .with_value(Value::Percent(mem_perc)),
+    )
+}
+
+/// The percentage of memory in use, according to the `/proc/meminfo` of the sysroot
+/// (the memory not available to start new applications, as sysinfo reports it).
+async fn meminfo_usage(opts: &Options) -> Option<f64> {
+    let meminfo = tokio::fs::read_to_string(opts.sys_path("/proc/meminfo"))
+        .await
+        .ok()?;
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|value| {
+                value
+                    .trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+    };
+    let total = field("MemTotal").filter(|&total| total > 0)?;
+    let available = field("MemAvailable")?;
+    Some(total.saturating_sub(available) as f64 / total as f64 * 100.0)
 }
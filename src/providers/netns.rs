@@ -11,3 +11,24 @@ pub async fn show(opts: &Options) -> Option<Chunk<SmolStr>> {
         .filter(|s| !s.is_empty())
         .map(|s| Chunk::new(opts.select_str("{}", ""), s))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::fixture::Fixture;
+
+    #[tokio::test]
+    async fn test_netns() {
+        let opts = Options {
+            netns: true,
+            ..Default::default()
+        };
+        let fixture = Fixture::new().with("ip netns identify", "blue\n");
+        let chunk = fixture.scope(show(&opts)).await.unwrap();
+        assert_eq!(chunk.info_str(), Some("blue"));
+
+        // the default namespace
+        let fixture = Fixture::new().with("ip netns identify", "");
+        assert!(fixture.scope(show(&opts)).await.is_none());
+    }
+}
@@ -80,8 +80,10 @@ static OS_MAP: phf::Map<&'static str, OsInfo> = phf_map! {
 };
 
 // see few examples @ https://gist.github.com/natefoo/814c5bf936922dad97ff
-pub async fn lsb_icon() -> Option<Chunk<Unit>> {
-    let os_release = tokio::fs::read_to_string("/etc/os-release").await.ok()?;
+pub async fn lsb_icon(opts: &Options) -> Option<Chunk<Unit>> {
+    let os_release = tokio::fs::read_to_string(opts.sys_path("/etc/os-release"))
+        .await
+        .ok()?;
     let id_line = os_release.lines().find(|line| line.starts_with("ID="))?;
     let id = id_line.trim_start_matches("ID=").trim_matches('"');
     OS_MAP
@@ -96,7 +98,7 @@ pub async fn show(opts: &Options) -> Option<Chunk<Unit>> {
     }
 
     match std::env::consts::OS {
-        "linux" => lsb_icon().await,
+        "linux" => lsb_icon(opts).await,
         "windows" => OS_MAP.get("windows").map(|info| Chunk::icon(info.icon)),
         "macos" => OS_MAP.get("apple").map(|info| Chunk::icon(info.icon)),
         "ios" => OS_MAP.get("apple").map(|info| Chunk::icon(info.icon)),
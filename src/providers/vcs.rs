@@ -3,9 +3,12 @@ pub mod git;
 pub mod hg;
 pub mod jj;
 pub mod pijul;
+pub mod remote;
 
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use crate::value::{LineCounts, Value};
 use crate::{chunk::Chunk, options::Options, style::to_superscript};
 
 use crate::providers::vcs::darcs::Darcs;
@@ -17,17 +20,40 @@ use crate::providers::vcs::pijul::Pijul;
 use enum_dispatch::enum_dispatch;
 use itertools::Itertools;
 use smallvec::SmallVec;
-use smol_str::{SmolStr, SmolStrBuilder};
+use smol_str::{format_smolstr, SmolStr, SmolStrBuilder};
 use tokio::fs;
 
 #[enum_dispatch]
 pub trait VcsTrait {
     async fn branch(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
+    /// The hosting service and the slug of the repository of the default remote.
+    async fn remote(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
+    /// The operation in progress (rebase, merge, ...), if any.
+    async fn operation(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
     async fn commit(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
+    /// How long ago the last commit was made and, with `--commit-summary`, its subject and
+    /// its author.
+    async fn age(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
     async fn status(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
+    /// The submodules (or subrepositories) that are not checked out as recorded, if any.
+    async fn submodules(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
+    /// What the working tree or the object database leaves out: a sparse checkout, a
+    /// shallow or partial clone, large files not fetched.
+    async fn partial(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
+    /// The lines inserted and deleted by the changes of the working copy, if any.
+    async fn diff(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
     async fn worktree(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
     async fn stash(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
     async fn divergence(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
+    /// The commits ahead and behind `@{push}`, when it is not the upstream.
+    async fn push(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
+    /// The commits ahead and behind the base branch of the options.
+    async fn base(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
+    /// How long ago the remotes were last fetched, past the configured threshold.
+    async fn fetch(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>>;
+    /// The command (program and arguments) fetching the remotes, run in the background
+    /// by `--auto-fetch`.
+    fn fetch_command(&self) -> Option<&'static [&'static str]>;
 }
 
 #[enum_dispatch(VcsTrait)]
@@ -62,7 +88,7 @@ impl<T> AsRef<str> for StatusIcon<T> {
 }
 
 pub async fn infer_vcs(start: PathBuf, opts: &Options) -> Option<(Vcs, PathBuf)> {
-    if !opts.vcs {
+    if !opts.is_enabled("vcs") {
         return None;
     }
 
@@ -106,6 +132,97 @@ pub fn merge_icons<T: AsRef<str>, const N: usize>(icons: SmallVec<[T; N]>) -> Sm
     builder.finish()
 }
 
+/// The lines inserted and deleted from the summary of `diff --stat` (e.g.
+/// `2 files changed, 10 insertions(+), 3 deletions(-)`).
+pub fn parse_diffstat(stat: &str) -> LineCounts {
+    let mut counts = LineCounts::default();
+    let Some(summary) = stat.lines().rev().find(|l| l.contains(" changed")) else {
+        return counts;
+    };
+    for part in summary.split(',') {
+        let mut words = part.split_whitespace();
+        let n = words.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+        match words.next() {
+            Some(w) if w.starts_with("insertion") => counts.insertions = n,
+            Some(w) if w.starts_with("deletion") => counts.deletions = n,
+            _ => {}
+        }
+    }
+    counts
+}
+
+/// The chunk of the lines changed in the working copy (`+120 -34`), followed by those of
+/// the staged changes (`●+10 -2`).
+pub fn render_diff(unstaged: LineCounts, staged: LineCounts) -> Option<Chunk<SmolStr>> {
+    let render = |prefix, counts: LineCounts| match (counts.insertions, counts.deletions) {
+        (0, 0) => None,
+        (i, 0) => Some(format_smolstr!("{prefix}+{i}")),
+        (0, d) => Some(format_smolstr!("{prefix}-{d}")),
+        (i, d) => Some(format_smolstr!("{prefix}+{i} -{d}")),
+    };
+    let info = [render("", unstaged), render("●", staged)]
+        .into_iter()
+        .flatten()
+        .join(" ");
+    (!info.is_empty())
+        .then(|| Chunk::info(info.into()).with_value(Value::Diff { unstaged, staged }))
+}
+
+/// The last commit of the working copy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LastCommit {
+    /// The commit time, in seconds since the epoch.
+    pub time: i64,
+    pub author: SmolStr,
+    /// The first line of the description.
+    pub subject: SmolStr,
+}
+
+/// The subjects longer than this are truncated.
+const MAX_SUBJECT: usize = 32;
+
+/// The chunk of the age of the last commit (`3h`) and, with `--commit-summary`, of its
+/// truncated subject and of its author unless it is the current `user`.
+pub fn render_age(opts: &Options, commit: &LastCommit, user: Option<&str>) -> Chunk<SmolStr> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let age = Duration::from_secs(now.saturating_sub(commit.time).max(0) as u64);
+
+    let mut info = format_age(age);
+    if opts.commit_summary {
+        if !commit.subject.is_empty() {
+            let subject: SmolStr = match commit.subject.char_indices().nth(MAX_SUBJECT) {
+                Some((end, _)) => format_smolstr!("{}…", commit.subject[..end].trim_end()),
+                None => commit.subject.clone(),
+            };
+            info = format_smolstr!("{info} {subject}");
+        }
+        if !commit.author.is_empty() && user != Some(commit.author.as_str()) {
+            info = format_smolstr!("{info} ({})", commit.author);
+        }
+    }
+    Chunk::new("◷", info).with_value(Value::Duration(age))
+}
+
+/// A duration in its largest unit: `45s`, `3m`, `3h`, `2d`, `5mo` or `2y`.
+pub fn format_age(age: Duration) -> SmolStr {
+    const UNITS: [(u64, &str); 6] = [
+        (365 * 86400, "y"),
+        (30 * 86400, "mo"),
+        (86400, "d"),
+        (3600, "h"),
+        (60, "m"),
+        (1, "s"),
+    ];
+    let secs = age.as_secs();
+    let (unit, name) = UNITS
+        .into_iter()
+        .find(|&(unit, _)| secs >= unit)
+        .unwrap_or((1, "s"));
+    format_smolstr!("{}{name}", secs / unit)
+}
+
 pub fn render_icon<T: AsRef<str>>((icon, n): (T, usize)) -> SmolStr {
     let mut builder = SmolStrBuilder::new();
     if n == 1 {
@@ -118,3 +235,159 @@ pub fn render_icon<T: AsRef<str>>((icon, n): (T, usize)) -> SmolStr {
     }
     builder.finish()
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A temporary directory, removed when dropped.
+    pub struct TempRepo(PathBuf);
+
+    impl std::ops::Deref for TempRepo {
+        type Target = PathBuf;
+
+        fn deref(&self) -> &PathBuf {
+            &self.0
+        }
+    }
+
+    impl Drop for TempRepo {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A fresh directory with the given files, standing for the root of a repository.
+    pub fn temp_repo(name: &str, files: &[(&str, &[u8])]) -> TempRepo {
+        let dir = std::env::temp_dir().join(format!("auraline-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (file, content) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        TempRepo(dir)
+    }
+
+    /// The icon and the info of the chunk, if any.
+    pub fn parts(chunk: Option<Chunk<SmolStr>>) -> Option<(Option<String>, Option<String>)> {
+        chunk.map(|c| (c.icon_str().map(Into::into), c.info_str().map(Into::into)))
+    }
+
+    #[test]
+    fn test_merge_icons() {
+        let icons: SmallVec<[_; 8]> = ["●", "", "⁇", "●", "●", "✚"].into_iter().collect();
+        assert_eq!(merge_icons(icons), "⁇●³✚");
+        assert_eq!(merge_icons(SmallVec::<[&str; 1]>::new()), "");
+    }
+
+    #[test]
+    fn test_parse_diffstat() {
+        let stat =
+            " src/a.rs | 12 ++++++++----\n 2 files changed, 10 insertions(+), 3 deletions(-)";
+        let counts = LineCounts {
+            insertions: 10,
+            deletions: 3,
+        };
+        assert_eq!(parse_diffstat(stat), counts);
+        let stat = "1 file changed, 1 insertion(+)";
+        assert_eq!(parse_diffstat(stat).insertions, 1);
+        assert_eq!(parse_diffstat(""), LineCounts::default());
+    }
+
+    #[test]
+    fn test_render_diff() {
+        let counts = |insertions, deletions| LineCounts {
+            insertions,
+            deletions,
+        };
+        let info = |unstaged, staged| {
+            render_diff(unstaged, staged).map(|c| c.info_str().map(String::from))
+        };
+        assert_eq!(
+            info(counts(120, 34), counts(0, 0)),
+            Some(Some("+120 -34".into()))
+        );
+        assert_eq!(
+            info(counts(0, 2), counts(5, 0)),
+            Some(Some("-2 ●+5".into()))
+        );
+        assert_eq!(info(counts(0, 0), counts(0, 0)), None);
+    }
+
+    #[test]
+    fn test_render_age() {
+        for (secs, age) in [
+            (0, "0s"),
+            (45, "45s"),
+            (200, "3m"),
+            (3 * 3600 + 59, "3h"),
+            (2 * 86400, "2d"),
+            (90 * 86400, "3mo"),
+            (800 * 86400, "2y"),
+        ] {
+            assert_eq!(format_age(Duration::from_secs(secs)), age);
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let commit = LastCommit {
+            time: now.as_secs() as i64 - 3 * 3600 - 30,
+            author: "alice".into(),
+            subject: "Fix the race condition in the file watcher initialization".into(),
+        };
+        let chunk = render_age(&Options::default(), &commit, None);
+        assert_eq!(chunk.info_str(), Some("3h"));
+        assert!(matches!(chunk.value(), Some(Value::Duration(d)) if d.as_secs() >= 3 * 3600));
+
+        let opts = Options {
+            commit_summary: true,
+            ..Default::default()
+        };
+        let chunk = render_age(&opts, &commit, Some("bob"));
+        let expected = "3h Fix the race condition in the fi… (alice)";
+        assert_eq!(chunk.info_str(), Some(expected));
+        let chunk = render_age(&opts, &commit, Some("alice"));
+        assert_eq!(
+            chunk.info_str(),
+            Some("3h Fix the race condition in the fi…")
+        );
+
+        // committed in the future (clock skew)
+        let commit = LastCommit {
+            time: now.as_secs() as i64 + 60,
+            ..Default::default()
+        };
+        assert_eq!(render_age(&opts, &commit, None).info_str(), Some("0s"));
+    }
+
+    #[test]
+    fn test_render_icon() {
+        assert_eq!(render_icon(("●", 1)), "●");
+        assert_eq!(render_icon(("●", 12)), "●¹²");
+    }
+
+    #[tokio::test]
+    async fn test_infer_vcs() {
+        let opts = Options {
+            vcs: true,
+            ..Default::default()
+        };
+        let root = temp_repo("infer", &[("outer/.hg/branch", b"default")]);
+        std::fs::create_dir_all(root.join("outer/inner/.git")).unwrap();
+        std::fs::create_dir_all(root.join("outer/inner/src")).unwrap();
+
+        let (vcs, dir) = infer_vcs(root.join("outer/inner/src"), &opts)
+            .await
+            .unwrap();
+        assert_eq!(vcs, Vcs::Git(Git));
+        assert_eq!(dir, root.join("outer/inner").canonicalize().unwrap());
+
+        let (vcs, _) = infer_vcs(root.join("outer"), &opts).await.unwrap();
+        assert_eq!(vcs, Vcs::Hg(Hg));
+
+        assert!(infer_vcs(root.join("outer"), &Options::default())
+            .await
+            .is_none());
+    }
+}
@@ -22,6 +22,14 @@ impl VcsTrait for Darcs {
         None
     }
 
+    async fn remote(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn operation(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
     async fn commit(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         darcs!("log", "--last", "1", "--summary")
             .await?
@@ -32,6 +40,10 @@ impl VcsTrait for Darcs {
             .map(|s| Chunk::new("⭑", s.trim().to_smolstr()))
     }
 
+    async fn age(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
     async fn status(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         darcs!("whatsnew", "-s")
             .await
@@ -45,6 +57,18 @@ impl VcsTrait for Darcs {
             })
     }
 
+    async fn diff(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn submodules(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn partial(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
     async fn worktree(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         None
     }
@@ -56,6 +80,22 @@ impl VcsTrait for Darcs {
     async fn divergence(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         None
     }
+
+    async fn push(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn base(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn fetch(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    fn fetch_command(&self) -> Option<&'static [&'static str]> {
+        None
+    }
 }
 
 impl FromStr for StatusIcon<Darcs> {
@@ -74,3 +114,64 @@ impl FromStr for StatusIcon<Darcs> {
         Ok(icon)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::fixture::Fixture;
+    use crate::providers::vcs::tests::parts;
+
+    #[tokio::test]
+    async fn test_commit() {
+        let fixture = Fixture::new().with(
+            "darcs log --last 1 --summary",
+            "patch 5d3c0a9e1b7f4c2d8e6a0b9c7d5e3f1a2b4c6d8e\nAuthor: user\nDate:   Wed May  1 10:00:00 CEST 2024\n  * initial\n\n    A ./a.rs\n",
+        );
+        let chunk = fixture
+            .scope(Darcs.commit(&Options::default(), Path::new(".")))
+            .await;
+        assert_eq!(
+            parts(chunk),
+            Some((
+                Some("⭑".into()),
+                Some("5d3c0a9e1b7f4c2d8e6a0b9c7d5e3f1a2b4c6d8e".into())
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let fixture = Fixture::new().with(
+            "darcs whatsnew -s",
+            "M ./a.rs -1 +2\nM ./b.rs +3\nA ./c.rs\nR ./d.rs\n",
+        );
+        let chunk = fixture
+            .scope(Darcs.status(&Options::default(), Path::new(".")))
+            .await;
+        assert_eq!(parts(chunk), Some((None, Some("−●²✚".into()))));
+
+        // no changes: `darcs whatsnew` fails
+        assert!(Fixture::new()
+            .scope(Darcs.status(&Options::default(), Path::new(".")))
+            .await
+            .is_none());
+    }
+
+    #[test]
+    fn test_status_icon() {
+        for (line, icon) in [
+            ("R ./a", "−"),
+            ("A ./a", "✚"),
+            ("M ./a -1 +1", "●"),
+            ("F ./a ./b", "→"),
+            ("T ./a", "→"),
+            ("No changes!", ""),
+        ] {
+            assert_eq!(
+                line.parse::<StatusIcon<Darcs>>().unwrap().value,
+                icon,
+                "{line}"
+            );
+        }
+    }
+}
@@ -1,14 +1,34 @@
+mod graph;
+mod odb;
+mod porcelain;
+mod repo;
+
 use crate::chunk::Chunk;
-use crate::providers::vcs::{merge_icons, StatusIcon, VcsTrait};
+use crate::options::GitBackend;
+use crate::providers::vcs::git::porcelain::Porcelain;
+use crate::providers::vcs::git::repo::{common_dir, config_files, parse_config, Head, Repository};
+use crate::providers::vcs::remote::Remote;
+use crate::providers::vcs::{
+    format_age, merge_icons, render_age, render_diff, LastCommit, StatusIcon, VcsTrait,
+};
 use crate::style::to_superscript;
-use crate::{cmd::CMD, options::Options};
+use crate::value::{LineCounts, StatusCounts, SubmoduleCounts, Value};
+use crate::{
+    cmd::{git_dir, CMD},
+    options::Options,
+};
+use parking_lot::Mutex;
 use smallvec::SmallVec;
 use smol_str::{format_smolstr, SmolStr, StrExt, ToSmolStr};
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::join;
+use tokio::sync::OnceCell;
 
 macro_rules! git {
     ( $( $x:expr ),* ) => {
@@ -16,13 +36,109 @@ macro_rules! git {
     };
 }
 
+/// The age of the last fetch past which it is highlighted, unless configured.
+const STALE_FETCH: Duration = Duration::from_secs(7 * 86400);
+
+/// The status of a working tree, once run and parsed.
+type SharedStatus = Arc<OnceCell<Option<Arc<Porcelain>>>>;
+
+/// The repositories read in-process, opened once per prompt.
+#[derive(Default)]
+struct Repos(Mutex<HashMap<PathBuf, Option<Arc<Repository>>>>);
+
+/// The status of the working trees, run and parsed once per prompt.
+#[derive(Default)]
+struct Statuses(Mutex<HashMap<PathBuf, SharedStatus>>);
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Git;
 
+/// Run `f` on the repository of the working tree `path`, read in-process. `None` when
+/// the native backend is disabled or cannot tell, for the CLI to be run instead.
+async fn native<T, F>(opts: &Options, path: &Path, f: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Repository) -> Option<T> + Send + 'static,
+{
+    if opts.git_backend == Some(GitBackend::Cli) {
+        return None;
+    }
+    let path = path.to_path_buf();
+    let repos = CMD.state::<Repos>();
+    tokio::task::spawn_blocking(move || {
+        let repo = repos
+            .0
+            .lock()
+            .entry(path.clone())
+            .or_insert_with(|| Repository::open(&path).map(Arc::new))
+            .clone()?;
+        f(&repo)
+    })
+    .await
+    .ok()?
+}
+
+/// The status of the working tree `path`, shared by all the segments: the branch, the
+/// divergence from its upstream, the stash and the changed files.
+async fn porcelain(path: &Path) -> Option<Arc<Porcelain>> {
+    let status = CMD
+        .state::<Statuses>()
+        .0
+        .lock()
+        .entry(path.to_path_buf())
+        .or_default()
+        .clone();
+    status
+        .get_or_init(|| async {
+            git!("status", "--porcelain=v2", "--branch", "--show-stash")
+                .await
+                .map(|s| Arc::new(Porcelain::parse(&s)))
+        })
+        .await
+        .clone()
+}
+
+/// The icon of the branch: whether it is the default branch of `origin` (its HEAD).
+fn branch_icon(branch: &str, origin: Option<&str>) -> &'static str {
+    match branch.rsplit('/').next() {
+        local if local == origin.and_then(|o| o.rsplit('/').next()) => "⟝",
+        _ => "⎇",
+    }
+}
+
 impl VcsTrait for Git {
-    async fn branch(&self, opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
-        let icon = git_branch_icon(opts).await;
-        let info = git_branch_name(opts).await;
+    async fn branch(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let branch = native(opts, path, |repo| {
+            Some(match repo.head()? {
+                Head::Branch(branch) => {
+                    let name = branch.strip_prefix("refs/heads/").unwrap_or(&branch);
+                    let icon = repo.resolve(&branch).map(|_| {
+                        let origin = repo.symbolic_target("refs/remotes/origin/HEAD");
+                        branch_icon(name, origin.as_deref())
+                    });
+                    (icon, Some(name.to_smolstr()))
+                }
+                Head::Detached(_) => (Some("⚠"), None),
+            })
+        })
+        .await;
+        let (icon, info) = match branch {
+            Some(branch) => branch,
+            None => {
+                let (status, origin) = join!(porcelain(path), git_origin_head());
+                match status?.as_ref() {
+                    Porcelain {
+                        branch: Some(branch),
+                        born,
+                        ..
+                    } => (
+                        born.then(|| branch_icon(branch, origin.as_deref())),
+                        Some(branch.clone()),
+                    ),
+                    Porcelain { branch: None, .. } => (Some("⚠"), None),
+                }
+            }
+        };
         match (icon, info) {
             (None, None) => None,
             (Some(icon), None) => Some(Chunk::icon(icon)),
@@ -31,12 +147,35 @@ impl VcsTrait for Git {
         }
     }
 
-    async fn commit(&self, opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
-        let (name_rev, branch_name, descr) = join!(
-            git_name_rev(opts),
-            git_branch_name(opts),
-            git_describe_cmd(opts)
-        );
+    async fn remote(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        Some(Remote::parse(&git_remote_url(path)?)?.chunk(opts))
+    }
+
+    async fn operation(&self, _opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let (name, progress) = git_operation(&git_dir(path)?)?;
+        Some(Chunk::new(
+            "⧗",
+            match progress {
+                Some((step, total)) => format_smolstr!("{name} {step}/{total}"),
+                None => name.into(),
+            },
+        ))
+    }
+
+    async fn commit(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        // `describe --always` names any commit: `name-rev` is never needed
+        let describe = native(opts, path, |repo| match repo.head_commit() {
+            Some(head) => repo.describe(head).map(Some),
+            None => Some(None),
+        })
+        .await;
+        if let Some(describe) = describe {
+            return describe.map(|d| Chunk::info(format_describe(&d)));
+        }
+
+        let (name_rev, status, descr) =
+            join!(git_name_rev(opts), porcelain(path), git_describe_cmd(opts));
+        let branch_name = status.and_then(|s| s.branch.clone());
 
         match (branch_name, descr, name_rev) {
             (_, None, None) => None,
@@ -47,20 +186,115 @@ impl VcsTrait for Git {
         }
     }
 
-    async fn status(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
-        git!("status", "--porcelain")
-            .await
-            .filter(|s| !s.is_empty())
-            .map(|s| {
-                Chunk::info(merge_icons(
-                    s.lines()
-                        .map(|l| l.parse::<StatusIcon<Git>>().unwrap())
-                        .collect::<SmallVec<[_; 8]>>(),
-                ))
-            })
+    async fn age(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let commit = native(opts, path, |repo| repo.last_commit(&repo.head_commit()?)).await;
+        let commit = match commit {
+            Some(commit) => commit,
+            None => {
+                let log = git!("log", "-1", "--format=%ct%n%an%n%s").await?;
+                let mut lines = log.lines();
+                LastCommit {
+                    time: lines.next()?.parse().ok()?,
+                    author: lines.next().unwrap_or_default().into(),
+                    subject: lines.next().unwrap_or_default().into(),
+                }
+            }
+        };
+        let user = match opts.commit_summary {
+            true => git_dir(path).and_then(|git_dir| git_user_name(&git_dir)),
+            false => None,
+        };
+        Some(render_age(opts, &commit, user.as_deref()))
+    }
+
+    async fn status(&self, _opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let status = porcelain(path).await?;
+        let changes = status.changes.iter().map(SmolStr::as_str);
+        (!status.changes.is_empty()).then(|| {
+            Chunk::info(merge_icons(
+                changes
+                    .clone()
+                    .map(|c| c.parse::<StatusIcon<Git>>().unwrap())
+                    .collect::<SmallVec<[_; 8]>>(),
+            ))
+            .with_value(Value::Status(git_status_counts(changes)))
+        })
     }
 
-    async fn worktree(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+    async fn diff(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        let (unstaged, staged) = join!(
+            git!("diff", "--numstat"),
+            git!("diff", "--numstat", "--cached")
+        );
+        render_diff(git_numstat(&unstaged?), git_numstat(&staged?))
+    }
+
+    async fn submodules(&self, _opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let gitmodules = tokio::fs::read_to_string(path.join(".gitmodules")).await;
+        let gitmodules = parse_config(&gitmodules.ok()?);
+        let status = porcelain(path).await;
+        let mut counts = SubmoduleCounts::default();
+        let mut icons = SmallVec::<[_; 8]>::new();
+        // the submodules without a working tree of their own
+        for sub in gitmodules
+            .iter()
+            .filter(|(key, _)| key.starts_with("submodule.") && key.ends_with(".path"))
+            .flat_map(|(_, paths)| paths.last())
+        {
+            if !path.join(sub.as_str()).join(".git").exists() {
+                counts.uninitialized += 1;
+                icons.push("⊘");
+            }
+        }
+        for sub in status.iter().flat_map(|s| &s.submodules) {
+            if sub.commit {
+                counts.changed += 1;
+                icons.push("⇄");
+            }
+            if sub.modified || sub.untracked {
+                counts.dirty += 1;
+                icons.push("✱");
+            }
+        }
+
+        (!icons.is_empty())
+            .then(|| Chunk::new("⊡", merge_icons(icons)).with_value(Value::Submodules(counts)))
+    }
+
+    async fn partial(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let mut parts = git_partial(&git_dir(path)?);
+        // run with git, unlike the rest of the segment read from the repository
+        let missing = match opts.lfs {
+            true => git_lfs_missing().await,
+            false => None,
+        };
+        if let Some(missing) = missing.filter(|&n| n > 0) {
+            parts.push(format_smolstr!(
+                "lfs{}",
+                to_superscript(&missing.to_string())
+            ));
+        }
+        (!parts.is_empty()).then(|| Chunk::new("◔", parts.join(" ").into()))
+    }
+
+    async fn worktree(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let worktree = native(opts, path, |repo| {
+            if !repo.is_linked_worktree() {
+                return Some(None);
+            }
+            Some(Some(match repo.head()? {
+                Head::Branch(branch) => {
+                    let name = branch.strip_prefix("refs/heads/").unwrap_or(&branch);
+                    format_smolstr!("[{name}]")
+                }
+                Head::Detached(_) => "(detached HEAD)".into(),
+            }))
+        })
+        .await;
+        if let Some(worktree) = worktree {
+            return worktree.map(|name| Chunk::new("⌂", name));
+        }
+
         let path = env::current_dir().ok()?;
         let output = git!("worktree", "list").await?;
         output.lines().skip(1).find_map(|line| {
@@ -76,35 +310,190 @@ impl VcsTrait for Git {
         })
     }
 
-    async fn stash(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
-        git!("stash", "list")
-            .await
-            .filter(|s| !s.is_empty())
-            .map(|s| {
-                let mut buffer = itoa::Buffer::new();
-                let n = buffer.format(s.lines().count());
-                Chunk::info(format_smolstr!("≡{}", to_superscript(n)))
-            })
+    async fn stash(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let count = match native(opts, path, |repo| Some(repo.stash_count())).await {
+            Some(count) => count,
+            None => porcelain(path).await.map_or(0, |s| s.stash),
+        };
+        (count > 0).then(|| {
+            let mut buffer = itoa::Buffer::new();
+            let n = buffer.format(count);
+            Chunk::info(format_smolstr!("≡{}", to_superscript(n))).with_value(Value::Count(count))
+        })
     }
 
-    async fn divergence(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
-        let (ahead, behind) = join!(
-            git!("rev-list", "--count", "HEAD@{upstream}..HEAD"),
-            git!("rev-list", "--count", "HEAD..HEAD@{upstream}")
-        );
+    async fn divergence(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let tracking = native(opts, path, |repo| {
+            let Head::Branch(branch) = repo.head()? else {
+                return Some(None);
+            };
+            let Some(local) = repo.resolve(&branch) else {
+                return Some(None);
+            };
+            if !repo.tracks(&branch) {
+                return Some(Some(Tracking::Untracked));
+            }
+            match repo.upstream(&branch).and_then(|u| repo.resolve(&u)) {
+                Some(upstream) => repo
+                    .ahead_behind(local, upstream)
+                    .map(|(ahead, behind)| Some(Tracking::Diverged(ahead, behind))),
+                None => Some(Some(Tracking::Gone)),
+            }
+        })
+        .await;
+
+        let tracking = match tracking {
+            Some(tracking) => tracking?,
+            None => {
+                let status = porcelain(path).await?;
+                match (&status.upstream, status.divergence) {
+                    _ if !status.born || status.branch.is_none() => return None,
+                    (None, _) => Tracking::Untracked,
+                    (Some(_), None) => Tracking::Gone,
+                    (Some(_), Some((ahead, behind))) => Tracking::Diverged(ahead, behind),
+                }
+            }
+        };
+
+        match tracking {
+            // told by the push segment
+            Tracking::Untracked => None,
+            Tracking::Gone => Some(Chunk::info(SmolStr::new_static("[gone]"))),
+            Tracking::Diverged(ahead, behind) => divergence_info(ahead, behind, ["↑", "↓"])
+                .map(|info| Chunk::info(info).with_value(Value::Divergence { ahead, behind })),
+        }
+    }
+
+    async fn push(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let tracking = native(opts, path, |repo| {
+            let Head::Branch(branch) = repo.head()? else {
+                return Some(None);
+            };
+            let untracked = !repo.tracks(&branch);
+            let push = repo
+                .push_ref(&branch)
+                .filter(|push| untracked || Some(push) != repo.upstream(&branch).as_ref());
+            match (repo.resolve(&branch), push.and_then(|p| repo.resolve(&p))) {
+                (Some(local), Some(push)) => repo
+                    .ahead_behind(local, push)
+                    .map(|(ahead, behind)| Some(Tracking::Diverged(ahead, behind))),
+                _ => Some(untracked.then_some(Tracking::Untracked)),
+            }
+        })
+        .await;
+
+        let tracking = match tracking {
+            Some(tracking) => tracking?,
+            None => {
+                let branch = format!("refs/heads/{}", porcelain(path).await?.branch.as_ref()?);
+                let refs = git!(
+                    "for-each-ref",
+                    "--format=%(upstream)%00%(push)%00%(push:track,nobracket)",
+                    branch.as_str()
+                )
+                .await?;
+                parse_push_track(&refs)?
+            }
+        };
+
+        match tracking {
+            // only in the repositories with a remote to track
+            Tracking::Untracked => {
+                git_remote_url(path).map(|_| Chunk::info(SmolStr::new_static("↛")))
+            }
+            Tracking::Gone => None,
+            Tracking::Diverged(ahead, behind) => divergence_info(ahead, behind, ["⇡", "⇣"])
+                .map(|info| Chunk::info(info).with_value(Value::Divergence { ahead, behind })),
+        }
+    }
 
-        let ahead = ahead?;
-        let behind = behind?;
+    async fn fetch(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        // `FETCH_HEAD` is written in the git directory of the worktree that fetched
+        let git_dir = git_dir(path)?;
+        let mut fetched = None;
+        for dir in [common_dir(&git_dir), git_dir] {
+            let modified = tokio::fs::metadata(dir.join("FETCH_HEAD")).await;
+            fetched = fetched.max(modified.and_then(|m| m.modified()).ok());
+        }
+        let age = SystemTime::now()
+            .duration_since(fetched?)
+            .unwrap_or_default();
 
-        match (ahead.as_str(), behind.as_str()) {
-            ("0" | "", "0" | "") => None,
-            ("0" | "", behind) => Some(Chunk::info(format_smolstr!("↓{}", behind))),
-            (ahead, "0" | "") => Some(Chunk::info(format_smolstr!("↑{}", ahead))),
-            (ahead, behind) => Some(Chunk::info(format_smolstr!("↑{}↓{}", ahead, behind))),
+        let settings = &opts.settings.fetch;
+        if settings.min.is_some_and(|min| age < min) {
+            return None;
         }
+        let stale = age >= settings.stale.unwrap_or(STALE_FETCH);
+        Some(
+            Chunk::new("⟳", format_age(age))
+                .with_value(Value::Duration(age))
+                .with_alert(stale),
+        )
+    }
+
+    fn fetch_command(&self) -> Option<&'static [&'static str]> {
+        Some(&["git", "fetch", "--quiet"])
+    }
+
+    async fn base(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let base = opts.base_branch.clone()?;
+        let divergence = native(opts, path, {
+            let base = base.clone();
+            move |repo| repo.ahead_behind(repo.head_commit()?, repo.resolve_short(&base)?)
+        })
+        .await;
+
+        let (ahead, behind) = match divergence {
+            Some(divergence) => divergence,
+            None => {
+                let range = format!("HEAD...{base}");
+                let counts = git!("rev-list", "--left-right", "--count", range.as_str()).await?;
+                let (ahead, behind) = counts.split_once('\t')?;
+                (ahead.parse().ok()?, behind.parse().ok()?)
+            }
+        };
+        divergence_info(ahead, behind, ["↑", "↓"])
+            .map(|info| Chunk::new(base, info).with_value(Value::Divergence { ahead, behind }))
     }
 }
 
+/// The upstream of the current branch.
+enum Tracking {
+    /// No upstream is configured.
+    Untracked,
+    /// The upstream branch was deleted.
+    Gone,
+    Diverged(u64, u64),
+}
+
+/// The commits ahead and behind, e.g. `↑2↓3`; nothing when in sync.
+fn divergence_info(ahead: u64, behind: u64, [up, down]: [&str; 2]) -> Option<SmolStr> {
+    match (ahead, behind) {
+        (0, 0) => None,
+        (0, behind) => Some(format_smolstr!("{down}{behind}")),
+        (ahead, 0) => Some(format_smolstr!("{up}{ahead}")),
+        (ahead, behind) => Some(format_smolstr!("{up}{ahead}{down}{behind}")),
+    }
+}
+
+/// The commits ahead and behind `@{push}` out of the `%(upstream)`, `%(push)` and
+/// `%(push:track,nobracket)` of the branch, when it is pushed elsewhere than its upstream;
+/// else whether it has no upstream.
+fn parse_push_track(refs: &str) -> Option<Tracking> {
+    let mut fields = refs.split('\0');
+    let (upstream, push, track) = (fields.next()?, fields.next()?, fields.next()?);
+    if push.is_empty() || push == upstream || track == "gone" {
+        return upstream.is_empty().then_some(Tracking::Untracked);
+    }
+    let count = |key: &str| {
+        track
+            .split(", ")
+            .find_map(|t| t.strip_prefix(key)?.parse().ok())
+            .unwrap_or(0)
+    };
+    Some(Tracking::Diverged(count("ahead "), count("behind ")))
+}
+
 impl FromStr for StatusIcon<Git> {
     type Err = Infallible;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -164,31 +553,203 @@ impl FromStr for StatusIcon<Git> {
     }
 }
 
+/// The URL of the remote of the current branch, else of `origin`, else of the first one.
+fn git_remote_url(root: &Path) -> Option<SmolStr> {
+    let git_dir = git_dir(root)?;
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).unwrap_or_default();
+    let branch = head.trim().strip_prefix("ref: refs/heads/");
+    remote_url(&git_dir, branch)
+}
+
+/// The URL of the remote of `branch`, else of `origin`, else of the first one, in the
+/// configuration of the git directory `git_dir`.
+pub(super) fn remote_url(git_dir: &Path, branch: Option<&str>) -> Option<SmolStr> {
+    let config = std::fs::read_to_string(common_dir(git_dir).join("config")).ok()?;
+    let config = parse_config(&config);
+    let url = |remote: &str| config.get(format!("remote.{remote}.url").as_str())?.last();
+
+    branch
+        .and_then(|b| config.get(format!("branch.{b}.remote").as_str())?.last())
+        .and_then(|remote| url(remote))
+        .or_else(|| url("origin"))
+        .or_else(|| {
+            config
+                .iter()
+                .filter(|(key, _)| key.starts_with("remote.") && key.ends_with(".url"))
+                .min_by_key(|(key, _)| *key)
+                .and_then(|(_, urls)| urls.last())
+        })
+        .cloned()
+}
+
+/// The name git records as the author of the new commits: `GIT_AUTHOR_NAME`, else the
+/// `user.name` of the configuration of the worktree, of the repository, of the user.
+fn git_user_name(git_dir: &Path) -> Option<SmolStr> {
+    if let Ok(name) = env::var("GIT_AUTHOR_NAME") {
+        return Some(name.into());
+    }
+    config_files(git_dir).into_iter().find_map(|path| {
+        let config = parse_config(&std::fs::read_to_string(path).ok()?);
+        config.get("user.name")?.last().cloned()
+    })
+}
+
+/// What the repository leaves out: the working tree of a sparse checkout, the history of a
+/// shallow clone and the objects of a partial clone (fetched from a promisor remote).
+fn git_partial(git_dir: &Path) -> Vec<SmolStr> {
+    let common_dir = common_dir(git_dir);
+    let read = |path: PathBuf| {
+        std::fs::read_to_string(path)
+            .map(|config| parse_config(&config))
+            .unwrap_or_default()
+    };
+    let config = read(common_dir.join("config"));
+    let worktree = read(git_dir.join("config.worktree"));
+    let enabled = |values: &Vec<SmolStr>| {
+        values
+            .last()
+            .is_some_and(|v| matches!(v.to_ascii_lowercase().as_str(), "true" | "yes" | "on" | "1"))
+    };
+
+    let sparse = worktree
+        .get("core.sparsecheckout")
+        .or_else(|| config.get("core.sparsecheckout"))
+        .is_some_and(enabled)
+        && git_dir.join("info/sparse-checkout").exists();
+    let shallow = std::fs::metadata(common_dir.join("shallow")).is_ok_and(|m| m.len() > 0);
+    let partial = config.contains_key("extensions.partialclone")
+        || config.iter().any(|(key, values)| {
+            key.starts_with("remote.") && key.ends_with(".promisor") && enabled(values)
+        });
+
+    [
+        (sparse, "sparse"),
+        (shallow, "shallow"),
+        (partial, "partial"),
+    ]
+    .into_iter()
+    .filter(|(on, _)| *on)
+    .map(|(_, name)| SmolStr::new_static(name))
+    .collect()
+}
+
+/// The Git LFS objects of the working tree not fetched yet (`git lfs ls-files` marks them
+/// with `-`), whichever attributes file tracks them.
+async fn git_lfs_missing() -> Option<usize> {
+    let files = git!("lfs", "ls-files").await?;
+    Some(
+        files
+            .lines()
+            .filter(|line| line.split(' ').nth(1) == Some("-"))
+            .count(),
+    )
+}
+
+/// The operation in progress in the git directory, as `git status` tells it, with its
+/// step and number of steps when known (e.g. `REBASE 3/7`).
+fn git_operation(git_dir: &Path) -> Option<(&'static str, Option<(u64, u64)>)> {
+    let number = |dir: &Path, file| {
+        std::fs::read_to_string(dir.join(file))
+            .ok()?
+            .trim()
+            .parse::<u64>()
+            .ok()
+    };
+    let progress = |dir: &Path, step, total| number(dir, step).zip(number(dir, total));
+
+    let merge = git_dir.join("rebase-merge");
+    if merge.is_dir() {
+        return Some(("REBASE", progress(&merge, "msgnum", "end")));
+    }
+    let apply = git_dir.join("rebase-apply");
+    if apply.is_dir() {
+        let name = if apply.join("rebasing").exists() {
+            "REBASE"
+        } else if apply.join("applying").exists() {
+            "AM"
+        } else {
+            "AM/REBASE"
+        };
+        return Some((name, progress(&apply, "next", "last")));
+    }
+
+    [
+        ("MERGE_HEAD", "MERGE"),
+        ("CHERRY_PICK_HEAD", "CHERRY-PICK"),
+        ("REVERT_HEAD", "REVERT"),
+        ("BISECT_LOG", "BISECT"),
+    ]
+    .into_iter()
+    .find(|(file, _)| git_dir.join(file).exists())
+    .map(|(_, name)| (name, None))
+}
+
+/// The lines inserted and deleted according to `git diff --numstat` (`-` for the binary
+/// files).
+fn git_numstat(numstat: &str) -> LineCounts {
+    let mut counts = LineCounts::default();
+    for line in numstat.lines() {
+        let mut fields = line.split('\t');
+        let mut next = || {
+            fields
+                .next()
+                .and_then(|n| n.parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+        counts.insertions += next();
+        counts.deletions += next();
+    }
+    counts
+}
+
+/// Count the files in each state from their `XY` codes of `git status --porcelain`.
+fn git_status_counts<'a>(codes: impl IntoIterator<Item = &'a str>) -> StatusCounts {
+    let mut counts = StatusCounts::default();
+    for code in codes {
+        let mut chars = code.chars();
+        let (x, y) = (chars.next().unwrap_or(' '), chars.next().unwrap_or(' '));
+        match (x, y) {
+            ('?', '?') => counts.untracked += 1,
+            ('!', '!') => {}
+            ('D', 'D') | ('A', 'A') | ('U', _) | (_, 'U') => counts.conflicted += 1,
+            (x, y) => {
+                counts.staged += u64::from(x != ' ');
+                counts.added += u64::from(x == 'A');
+                counts.modified += u64::from(matches!(x, 'M' | 'T') || matches!(y, 'M' | 'T'));
+                counts.deleted += u64::from(x == 'D' || y == 'D');
+                counts.renamed += u64::from(x == 'R' || y == 'R');
+                counts.copied += u64::from(x == 'C' || y == 'C');
+            }
+        }
+    }
+    counts
+}
+
 async fn git_describe_cmd(_opts: &Options) -> Option<SmolStr> {
     git!("describe", "--abbrev=8", "--always", "--tag", "--long")
         .await
-        .map(|s| {
-            let output = s.trim().split('-').collect::<SmallVec<[_; 4]>>();
-            match output[..] {
-                [] => "".to_smolstr(),
-                [tag] => tag.to_smolstr(),
-                [tag, "0"] => tag.to_smolstr(),
-                [tag, n] => format_smolstr!("{tag}▴{n}"),
-                [tag, "0", hash] => format_smolstr!("{tag}∷{}", &hash[1..]),
-                [tag, n, hash, ..] => format_smolstr!("{tag}▴{n}∷{}", &hash[1..]),
-            }
-        })
+        .map(|s| format_describe(&s))
 }
 
-async fn git_rev_parse(origin: bool) -> Option<SmolStr> {
-    git!(
-        "rev-parse",
-        "--abbrev-ref",
-        if origin { "origin/HEAD" } else { "HEAD" }
-    )
-    .await
-    .filter(|s| !s.is_empty())
-    .and_then(|s| s.trim().split('/').next_back().map(Into::into))
+/// Render the output of `git describe --long` (`tag-N-gHASH`).
+fn format_describe(describe: &str) -> SmolStr {
+    let output = describe.trim().split('-').collect::<SmallVec<[_; 4]>>();
+    match output[..] {
+        [] => "".to_smolstr(),
+        [tag] => tag.to_smolstr(),
+        [tag, "0"] => tag.to_smolstr(),
+        [tag, n] => format_smolstr!("{tag}▴{n}"),
+        [tag, "0", hash] => format_smolstr!("{tag}∷{}", &hash[1..]),
+        [tag, n, hash, ..] => format_smolstr!("{tag}▴{n}∷{}", &hash[1..]),
+    }
+}
+
+/// The default branch of `origin`, if known.
+async fn git_origin_head() -> Option<SmolStr> {
+    git!("rev-parse", "--abbrev-ref", "origin/HEAD")
+        .await
+        .filter(|s| !s.is_empty())
+        .and_then(|s| s.trim().split('/').next_back().map(Into::into))
 }
 
 async fn git_name_rev(_opts: &Options) -> Option<SmolStr> {
@@ -209,21 +770,812 @@ fn git_bidirectional_inclusion(a: &SmolStr, b: &SmolStr) -> bool {
     a.contains(b.as_str()) || b.contains(a.as_str())
 }
 
-#[inline]
-async fn git_branch_icon(_: &Options) -> Option<&'static str> {
-    let (local, origin) = join!(git_rev_parse(false), git_rev_parse(true));
-    match local.as_deref() {
-        None => None,
-        Some("HEAD") => Some("⚠"),
-        Some(local) if Some(local) == origin.as_deref() => Some("⟝"),
-        _ => Some("⎇"),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::fixture::Fixture;
+    use crate::providers::vcs::git::odb::{to_hex, ObjectId};
+    use crate::providers::vcs::tests::{parts, temp_repo, TempRepo};
+
+    /// The options of the tests of the CLI backend.
+    fn cli() -> Options {
+        Options {
+            git_backend: Some(GitBackend::Cli),
+            ..Default::default()
+        }
     }
-}
 
-#[inline]
-async fn git_branch_name(_: &Options) -> Option<SmolStr> {
-    git!("branch", "--show")
-        .await
-        .filter(|s| !s.is_empty())
-        .map(|s| s.trim().to_smolstr())
+    /// The status, parsed once per repository and per fixture.
+    const STATUS: &str = "git status --porcelain=v2 --branch --show-stash";
+
+    /// The header of the status of a repository on `branch`.
+    fn header(branch: &str) -> String {
+        format!("# branch.oid {}\n# branch.head {branch}\n", "ab".repeat(20))
+    }
+
+    fn some(icon: Option<&str>, info: Option<&str>) -> Option<(Option<String>, Option<String>)> {
+        Some((icon.map(Into::into), info.map(Into::into)))
+    }
+
+    #[tokio::test]
+    async fn test_branch() {
+        let opts = cli();
+        let repo = temp_repo("git-branch", &[]);
+        let fixture = Fixture::new()
+            .with(STATUS, &header("main"))
+            .with("git rev-parse --abbrev-ref origin/HEAD", "origin/main\n");
+        let chunk = fixture.scope(Git.branch(&opts, &repo)).await;
+        assert_eq!(parts(chunk), some(Some("⟝"), Some("main")));
+
+        let fixture = Fixture::new()
+            .with(STATUS, &header("feature"))
+            .with("git rev-parse --abbrev-ref origin/HEAD", "origin/main");
+        let chunk = fixture.scope(Git.branch(&opts, &repo)).await;
+        assert_eq!(parts(chunk), some(Some("⎇"), Some("feature")));
+
+        // unborn branch
+        let fixture = Fixture::new().with(STATUS, "# branch.oid (initial)\n# branch.head main");
+        let chunk = fixture.scope(Git.branch(&opts, &repo)).await;
+        assert_eq!(parts(chunk), some(None, Some("main")));
+
+        // detached HEAD
+        let fixture = Fixture::new().with(STATUS, &header("(detached)"));
+        let chunk = fixture.scope(Git.branch(&opts, &repo)).await;
+        assert_eq!(parts(chunk), some(Some("⚠"), None));
+
+        // not a repository
+        let chunk = Fixture::new().scope(Git.branch(&opts, &repo)).await;
+        assert!(chunk.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_commit() {
+        let opts = cli();
+        let repo = temp_repo("git-commit", &[]);
+        let describe = "git describe --abbrev=8 --always --tag --long";
+        for (output, expected) in [
+            ("v1.0-3-gabcdef12", "v1.0▴3∷abcdef12"),
+            ("v1.0-0-gabcdef12", "v1.0∷abcdef12"),
+            ("abcdef12", "abcdef12"),
+        ] {
+            let fixture = Fixture::new().with(describe, output);
+            let chunk = fixture.scope(Git.commit(&opts, &repo)).await;
+            assert_eq!(parts(chunk), some(None, Some(expected)));
+        }
+
+        // without tags, the name of the revision relative to the branch
+        let fixture = Fixture::new()
+            .with("git name-rev --name-only HEAD", "remotes/origin/main~2")
+            .with(STATUS, &header("feature"));
+        let chunk = fixture.scope(Git.commit(&opts, &repo)).await;
+        assert_eq!(parts(chunk), some(None, Some("↪main↓2")));
+
+        // omitted when it is the branch itself
+        let fixture = Fixture::new()
+            .with("git name-rev --name-only HEAD", "main")
+            .with(STATUS, &header("main"));
+        let chunk = fixture.scope(Git.commit(&opts, &repo)).await;
+        assert!(chunk.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let opts = cli();
+        let repo = temp_repo("git-status", &[]);
+        let status = header("main")
+            + "1 M. N... 100644 100644 100644 aaaa bbbb staged.rs\n\
+               1 .M N... 100644 100644 100644 aaaa aaaa modified.rs\n\
+               2 R. N... 100644 100644 100644 aaaa aaaa R100 new.rs\told.rs\n\
+               ? a.txt\n? b.txt\n\
+               u UU N... 100644 100644 100644 100644 aaaa bbbb cccc conflict.rs\n";
+        let fixture = Fixture::new().with(STATUS, &status);
+        let chunk = fixture.scope(Git.status(&opts, &repo)).await.unwrap();
+        assert_eq!(chunk.info_str(), Some("⁇²→○●⚠"));
+        assert_eq!(
+            chunk.value(),
+            Some(&Value::Status(StatusCounts {
+                staged: 2,
+                modified: 2,
+                renamed: 1,
+                untracked: 2,
+                conflicted: 1,
+                ..Default::default()
+            }))
+        );
+
+        // clean working tree
+        let fixture = Fixture::new().with(STATUS, &header("main"));
+        assert!(fixture.scope(Git.status(&opts, &repo)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_operation() {
+        let opts = cli();
+        for (name, files, expected) in [
+            (
+                "git-rebase-merge",
+                &[
+                    (".git/rebase-merge/msgnum", &b"3\n"[..]),
+                    (".git/rebase-merge/end", b"7\n"),
+                ][..],
+                "REBASE 3/7",
+            ),
+            (
+                "git-rebase-apply",
+                &[
+                    (".git/rebase-apply/applying", b""),
+                    (".git/rebase-apply/next", b"1\n"),
+                    (".git/rebase-apply/last", b"2\n"),
+                ],
+                "AM 1/2",
+            ),
+            ("git-merge", &[(".git/MERGE_HEAD", b"")], "MERGE"),
+            (
+                "git-cherry-pick",
+                &[(".git/CHERRY_PICK_HEAD", b"")],
+                "CHERRY-PICK",
+            ),
+            ("git-bisect", &[(".git/BISECT_LOG", b"")], "BISECT"),
+        ] {
+            let repo = temp_repo(name, files);
+            let chunk = Git.operation(&opts, &repo).await;
+            assert_eq!(parts(chunk), some(Some("⧗"), Some(expected)), "{name}");
+        }
+
+        // in a linked worktree, the operations are those of its own git directory
+        let repo = temp_repo(
+            "git-operation-worktree",
+            &[
+                (".git", b"gitdir: main/.git/worktrees/wt\n"),
+                ("main/.git/MERGE_HEAD", b""),
+                ("main/.git/worktrees/wt/REVERT_HEAD", b""),
+            ],
+        );
+        let chunk = Git.operation(&opts, &repo).await;
+        assert_eq!(parts(chunk), some(Some("⧗"), Some("REVERT")));
+
+        let repo = temp_repo(
+            "git-no-operation",
+            &[(".git/HEAD", b"ref: refs/heads/main\n")],
+        );
+        assert!(Git.operation(&opts, &repo).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_partial() {
+        let opts = Options { lfs: true, ..cli() };
+        let repo = temp_repo(
+            "git-partial",
+            &[
+                (
+                    ".git/config",
+                    b"[core]\n\tsparseCheckout = true\n\
+                    [remote \"origin\"]\n\tpromisor = true\n\tpartialclonefilter = blob:none\n",
+                ),
+                (".git/info/sparse-checkout", b"/*\n!/vendor/\n"),
+                (
+                    ".git/shallow",
+                    b"1234567890abcdef1234567890abcdef12345678\n",
+                ),
+            ],
+        );
+        let fixture = Fixture::new().with(
+            "git lfs ls-files",
+            "0123456789 - a.bin\n1234567890 * b.bin\n2345678901 - c d.bin",
+        );
+        let chunk = fixture.scope(Git.partial(&opts, &repo)).await;
+        assert_eq!(
+            parts(chunk),
+            some(Some("◔"), Some("sparse shallow partial lfs²"))
+        );
+
+        // the large files counted only with --lfs
+        let chunk = Fixture::new()
+            .scope(Git.partial(&Options::default(), &repo))
+            .await;
+        assert_eq!(
+            parts(chunk),
+            some(Some("◔"), Some("sparse shallow partial"))
+        );
+
+        // a sparse checkout disabled, and all the large files fetched
+        let repo = temp_repo(
+            "git-partial-none",
+            &[
+                (".git/config", b"[core]\n\tsparseCheckout = false\n"),
+                (".git/info/sparse-checkout", b"/*\n"),
+            ],
+        );
+        let fixture = Fixture::new().with("git lfs ls-files", "0123456789 * a.bin");
+        assert!(fixture.scope(Git.partial(&opts, &repo)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_age() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap();
+        let log = format!("{}\nalice\nFix the parser", now.as_secs() - 2 * 86400 - 60);
+        let fixture = Fixture::new().with("git log -1 --format=%ct%n%an%n%s", &log);
+        let chunk = fixture.scope(Git.age(&cli(), Path::new("."))).await;
+        assert_eq!(parts(chunk), some(Some("◷"), Some("2d")));
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let repo = temp_repo("git-fetch", &[(".git/FETCH_HEAD", b"")]);
+        let fetched = |secs: u64| {
+            std::fs::File::options()
+                .write(true)
+                .open(repo.join(".git/FETCH_HEAD"))
+                .unwrap()
+                .set_modified(SystemTime::now() - Duration::from_secs(secs))
+                .unwrap();
+        };
+
+        fetched(4 * 86400 + 60);
+        let chunk = Git.fetch(&cli(), &repo).await.unwrap();
+        assert_eq!(chunk.info_str(), Some("4d"));
+        assert!(!chunk.is_alert());
+
+        fetched(8 * 86400);
+        assert!(Git.fetch(&cli(), &repo).await.unwrap().is_alert());
+
+        // the thresholds of the configuration
+        let mut opts = cli();
+        opts.settings.fetch.min = Some(Duration::from_secs(3600));
+        opts.settings.fetch.stale = Some(Duration::from_secs(86400));
+        fetched(120);
+        assert!(Git.fetch(&opts, &repo).await.is_none());
+        fetched(2 * 86400);
+        assert!(Git.fetch(&opts, &repo).await.unwrap().is_alert());
+
+        // never fetched
+        let repo = temp_repo(
+            "git-fetch-none",
+            &[(".git/HEAD", b"ref: refs/heads/main\n")],
+        );
+        assert!(Git.fetch(&cli(), &repo).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_remote() {
+        let config = "[remote \"origin\"]\n\turl = git@github.com:awgn/auraline.git\n\
+            [remote \"fork\"]\n\turl = https://gitlab.com/me/auraline\n\
+            [branch \"feature\"]\n\tremote = fork\n";
+        let repo = temp_repo(
+            "git-remote",
+            &[
+                (".git/HEAD", b"ref: refs/heads/main\n"),
+                (".git/config", config.as_bytes()),
+            ],
+        );
+        let chunk = Git.remote(&cli(), &repo).await.unwrap();
+        assert_eq!(chunk.icon_str(), Some("github"));
+        assert_eq!(chunk.info_str(), Some("awgn/auraline"));
+        assert_eq!(chunk.link(), None);
+
+        // the remote of the branch, linked to its web page
+        let repo = temp_repo(
+            "git-remote-branch",
+            &[
+                (".git/HEAD", b"ref: refs/heads/feature\n"),
+                (".git/config", config.as_bytes()),
+            ],
+        );
+        let opts = Options {
+            hyperlinks: true,
+            ..cli()
+        };
+        let chunk = Git.remote(&opts, &repo).await.unwrap();
+        assert_eq!(chunk.icon_str(), Some("gitlab"));
+        assert_eq!(chunk.info_str(), Some("me/auraline"));
+        assert_eq!(chunk.link(), Some("https://gitlab.com/me/auraline"));
+
+        // a local remote
+        let repo = temp_repo(
+            "git-remote-local",
+            &[
+                (".git/HEAD", b"ref: refs/heads/main\n"),
+                (
+                    ".git/config",
+                    b"[remote \"origin\"]\n\turl = ../upstream.git\n",
+                ),
+            ],
+        );
+        assert!(Git.remote(&cli(), &repo).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_submodules() {
+        let gitmodules = "[submodule \"a\"]\n\tpath = vendor/a\n\turl = ../a.git\n\
+            [submodule \"b\"]\n\tpath = vendor/b\n\turl = ../b.git\n\
+            [submodule \"c\"]\n\tpath = vendor/c\n\turl = ../c.git\n";
+        let repo = temp_repo(
+            "git-submodules",
+            &[
+                (".gitmodules", gitmodules.as_bytes()),
+                ("vendor/a/.git", b"gitdir: ../../.git/modules/a\n"),
+                ("vendor/b/.git", b"gitdir: ../../.git/modules/b\n"),
+            ],
+        );
+        let status = header("main")
+            + "1 .M SCM. 160000 160000 160000 aaaa aaaa vendor/a\n\
+               1 .M S..U 160000 160000 160000 aaaa aaaa vendor/b\n";
+        let fixture = Fixture::new().with(STATUS, &status);
+        let chunk = fixture.scope(Git.submodules(&cli(), &repo)).await.unwrap();
+        assert_eq!(chunk.icon_str(), Some("⊡"));
+        assert_eq!(chunk.info_str(), Some("⇄⊘✱²"));
+        assert_eq!(
+            chunk.value(),
+            Some(&Value::Submodules(SubmoduleCounts {
+                uninitialized: 1,
+                changed: 1,
+                dirty: 2,
+            }))
+        );
+
+        // all the submodules checked out as recorded
+        let repo = temp_repo(
+            "git-submodules-clean",
+            &[
+                (".gitmodules", gitmodules.as_bytes()),
+                ("vendor/a/.git", b"gitdir: ../../.git/modules/a\n"),
+                ("vendor/b/.git", b"gitdir: ../../.git/modules/b\n"),
+                ("vendor/c/.git", b"gitdir: ../../.git/modules/c\n"),
+            ],
+        );
+        let fixture = Fixture::new().with(STATUS, &header("main"));
+        let chunk = fixture.scope(Git.submodules(&cli(), &repo)).await;
+        assert!(chunk.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_diff() {
+        let opts = cli();
+        let fixture = Fixture::new()
+            .with(
+                "git diff --numstat",
+                "100\t30\tsrc/a.rs\n20\t4\tsrc/b.rs\n-\t-\tlogo.png",
+            )
+            .with("git diff --numstat --cached", "10\t2\tsrc/c.rs");
+        let chunk = fixture
+            .scope(Git.diff(&opts, Path::new(".")))
+            .await
+            .unwrap();
+        assert_eq!(chunk.info_str(), Some("+120 -34 ●+10 -2"));
+
+        // no changes
+        let fixture = Fixture::new()
+            .with("git diff --numstat", "")
+            .with("git diff --numstat --cached", "");
+        assert!(fixture
+            .scope(Git.diff(&opts, Path::new(".")))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_worktree() {
+        let opts = cli();
+        let cwd = env::current_dir().unwrap();
+        let list = format!(
+            "/srv/main      1234abcd [main]\n{}  5678ef90 [feature]\n",
+            cwd.display()
+        );
+        let fixture = Fixture::new().with("git worktree list", &list);
+        let chunk = fixture.scope(Git.worktree(&opts, Path::new("."))).await;
+        assert_eq!(parts(chunk), some(Some("⌂"), Some("[feature]")));
+
+        // the main worktree
+        let fixture = Fixture::new().with("git worktree list", "/srv/main 1234abcd [main]");
+        assert!(fixture
+            .scope(Git.worktree(&opts, Path::new(".")))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stash() {
+        let opts = cli();
+        let repo = temp_repo("git-stash", &[]);
+        let fixture = Fixture::new().with(STATUS, &(header("main") + "# stash 2\n"));
+        let chunk = fixture.scope(Git.stash(&opts, &repo)).await.unwrap();
+        assert_eq!(chunk.info_str(), Some("≡²"));
+        assert_eq!(chunk.value(), Some(&Value::Count(2)));
+
+        let fixture = Fixture::new().with(STATUS, &header("main"));
+        assert!(fixture.scope(Git.stash(&opts, &repo)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_divergence() {
+        let opts = cli();
+        let repo = temp_repo("git-divergence", &[]);
+        for (ab, expected) in [
+            ("+2 -0", Some("↑2")),
+            ("+0 -3", Some("↓3")),
+            ("+1 -3", Some("↑1↓3")),
+            ("+0 -0", None),
+        ] {
+            let status = header("main") + "# branch.upstream origin/main\n# branch.ab " + ab;
+            let fixture = Fixture::new().with(STATUS, &status);
+            let chunk = fixture.scope(Git.divergence(&opts, &repo)).await;
+            assert_eq!(chunk.as_ref().and_then(|c| c.info_str()), expected);
+        }
+
+        // no upstream, told by the push segment
+        let fixture = Fixture::new().with(STATUS, &header("main"));
+        let chunk = fixture.scope(Git.divergence(&opts, &repo)).await;
+        assert!(chunk.is_none());
+
+        // the upstream branch was deleted
+        let status = header("main") + "# branch.upstream origin/main\n";
+        let fixture = Fixture::new().with(STATUS, &status);
+        let chunk = fixture.scope(Git.divergence(&opts, &repo)).await;
+        assert_eq!(parts(chunk), some(None, Some("[gone]")));
+    }
+
+    #[tokio::test]
+    async fn test_push() {
+        let opts = cli();
+        let repo = temp_repo("git-push", &[]);
+        let for_each_ref = "git for-each-ref \
+            --format=%(upstream)%00%(push)%00%(push:track,nobracket) refs/heads/feature";
+        for (refs, expected) in [
+            (
+                "refs/remotes/origin/main\0refs/remotes/fork/feature\0ahead 2, behind 1",
+                Some("⇡2⇣1"),
+            ),
+            (
+                "refs/remotes/origin/main\0refs/remotes/fork/feature\0behind 3",
+                Some("⇣3"),
+            ),
+            // in sync, pushed to the upstream, and deleted
+            (
+                "refs/remotes/origin/main\0refs/remotes/fork/feature\0",
+                None,
+            ),
+            (
+                "refs/remotes/origin/main\0refs/remotes/origin/main\0ahead 1",
+                None,
+            ),
+            (
+                "refs/remotes/origin/main\0refs/remotes/fork/feature\0gone",
+                None,
+            ),
+        ] {
+            let fixture = Fixture::new()
+                .with(STATUS, &header("feature"))
+                .with(for_each_ref, refs);
+            let chunk = fixture.scope(Git.push(&opts, &repo)).await;
+            assert_eq!(
+                chunk.as_ref().and_then(|c| c.info_str()),
+                expected,
+                "{refs}"
+            );
+        }
+
+        // no upstream, in a repository without remotes
+        let fixture = Fixture::new()
+            .with(STATUS, &header("feature"))
+            .with(for_each_ref, "\0\0");
+        assert!(fixture.scope(Git.push(&opts, &repo)).await.is_none());
+
+        // no upstream, in a repository with a remote
+        let repo = temp_repo(
+            "git-push-untracked",
+            &[(".git/config", b"[remote \"origin\"]\n\turl = ../up.git\n")],
+        );
+        let fixture = Fixture::new()
+            .with(STATUS, &header("feature"))
+            .with(for_each_ref, "\0\0");
+        let chunk = fixture.scope(Git.push(&opts, &repo)).await;
+        assert_eq!(parts(chunk), some(None, Some("↛")));
+    }
+
+    #[tokio::test]
+    async fn test_base() {
+        let opts = Options {
+            base_branch: Some("origin/main".into()),
+            ..cli()
+        };
+        let fixture = Fixture::new().with(
+            "git rev-list --left-right --count HEAD...origin/main",
+            "3\t1",
+        );
+        let chunk = fixture.scope(Git.base(&opts, Path::new("."))).await;
+        assert_eq!(parts(chunk), some(Some("origin/main"), Some("↑3↓1")));
+
+        // no base branch
+        assert!(Fixture::new()
+            .scope(Git.base(&cli(), Path::new(".")))
+            .await
+            .is_none());
+    }
+
+    #[test]
+    fn test_status_icon() {
+        for (line, icon) in [
+            ("M  a", "●"),
+            (" M a", "○"),
+            ("MM a", "◉"),
+            ("A  a", "✚"),
+            (" D a", "ｘ"),
+            ("R  a -> b", "→"),
+            ("UU a", "⚠"),
+            ("?? a", "⁇"),
+            ("!! a", ""),
+            ("", ""),
+        ] {
+            assert_eq!(
+                line.parse::<StatusIcon<Git>>().unwrap().value,
+                icon,
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_status_counts() {
+        let counts = git_status_counts(["AM", "MD", "C ", "DD", "!!"]);
+        assert_eq!(
+            counts,
+            StatusCounts {
+                staged: 3,
+                added: 1,
+                modified: 2,
+                deleted: 1,
+                copied: 1,
+                conflicted: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    /// A loose commit object with the given parents, committed at `time`.
+    fn commit(id: ObjectId, parents: &[ObjectId], time: i64) -> (String, Vec<u8>) {
+        let mut content = String::from("tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n");
+        for parent in parents {
+            content += &format!("parent {}\n", to_hex(parent));
+        }
+        content += &format!("author a <a@b> {time} +0000\ncommitter a <a@b> {time} +0000\n\nc\n");
+        let object = format!("commit {}\0{content}", content.len());
+        let hex = to_hex(&id);
+        (
+            format!(".git/objects/{}/{}", &hex[..2], &hex[2..]),
+            miniz_oxide::deflate::compress_to_vec_zlib(object.as_bytes(), 6),
+        )
+    }
+
+    /// An annotated tag object of `object`, tagged at `time`.
+    fn tag(id: ObjectId, name: &str, object: ObjectId, time: i64) -> (String, Vec<u8>) {
+        let content = format!(
+            "object {}\ntype commit\ntag {name}\ntagger a <a@b> {time} +0000\n\n{name}\n",
+            to_hex(&object)
+        );
+        let object = format!("tag {}\0{content}", content.len());
+        let hex = to_hex(&id);
+        (
+            format!(".git/objects/{}/{}", &hex[..2], &hex[2..]),
+            miniz_oxide::deflate::compress_to_vec_zlib(object.as_bytes(), 6),
+        )
+    }
+
+    /// A repository on `main` (C1 ← C2 ← C3, with `v1` on C1 and the annotated `v2` on
+    /// C2) tracking `origin/main` (C1 ← C4), with two stashed changes.
+    fn native_repo(name: &str) -> (TempRepo, [ObjectId; 4]) {
+        let ids = [[0x11; 20], [0x22; 20], [0x33; 20], [0x44; 20]];
+        let hex = ids.map(|id| to_hex(&id));
+        let objects = [
+            commit(ids[0], &[], 1000),
+            commit(ids[1], &[ids[0]], 2000),
+            commit(ids[2], &[ids[1]], 3000),
+            commit(ids[3], &[ids[0]], 2500),
+            tag([0x55; 20], "v2", ids[1], 2000),
+        ];
+        let config = "[core]\n\tbare = false\n\
+            [remote \"origin\"]\n\turl = /srv/origin.git\n\
+            \tfetch = +refs/heads/*:refs/remotes/origin/*\n\
+            [branch \"main\"]\n\tremote = origin\n\tmerge = refs/heads/main\n";
+        let packed_refs = format!(
+            "# pack-refs with: peeled fully-peeled sorted\n\
+            {} refs/remotes/origin/main\n{} refs/tags/v1\n{} refs/tags/v2\n^{}\n",
+            hex[3],
+            hex[0],
+            "55".repeat(20),
+            hex[1]
+        );
+        let main = format!("{}\n", hex[2]);
+        let stash = format!(
+            "{0} {0} a <a@b> 1 +0000\tWIP\n{0} {0} a <a@b> 2 +0000\tWIP\n",
+            hex[2]
+        );
+
+        let mut files: Vec<(&str, &[u8])> = vec![
+            (".git/HEAD", b"ref: refs/heads/main\n"),
+            (".git/config", config.as_bytes()),
+            (".git/packed-refs", packed_refs.as_bytes()),
+            (".git/refs/heads/main", main.as_bytes()),
+            (
+                ".git/refs/remotes/origin/HEAD",
+                b"ref: refs/remotes/origin/main\n",
+            ),
+            (".git/logs/refs/stash", stash.as_bytes()),
+        ];
+        files.extend(
+            objects
+                .iter()
+                .map(|(path, data)| (path.as_str(), data.as_slice())),
+        );
+        (temp_repo(name, &files), ids)
+    }
+
+    #[tokio::test]
+    async fn test_native() {
+        let opts = Options::default();
+        let (repo, ids) = native_repo("git-native");
+        let abbrev = &to_hex(&ids[2])[..8];
+
+        // the CLI is never run: the fixture has no outputs
+        Fixture::new()
+            .scope(async {
+                let chunk = Git.branch(&opts, &repo).await;
+                assert_eq!(parts(chunk), some(Some("⟝"), Some("main")));
+
+                let chunk = Git.commit(&opts, &repo).await;
+                let expected = format!("v2▴1∷{abbrev}");
+                assert_eq!(parts(chunk), some(None, Some(&expected)));
+
+                let chunk = Git.divergence(&opts, &repo).await.unwrap();
+                assert_eq!(chunk.info_str(), Some("↑2↓1"));
+                let value = Value::Divergence {
+                    ahead: 2,
+                    behind: 1,
+                };
+                assert_eq!(chunk.value(), Some(&value));
+
+                let chunk = Git.stash(&opts, &repo).await;
+                assert_eq!(parts(chunk), some(None, Some("≡²")));
+
+                let commit = LastCommit {
+                    time: 3000,
+                    author: "a".into(),
+                    subject: "c".into(),
+                };
+                let repository = Repository::open(&repo).unwrap();
+                assert_eq!(repository.last_commit(&ids[2]), Some(commit));
+                let chunk = Git.age(&opts, &repo).await.unwrap();
+                assert!(chunk.info_str().unwrap().ends_with('y'));
+
+                assert!(Git.worktree(&opts, &repo).await.is_none());
+            })
+            .await;
+    }
+
+    /// A `feature` branch (C1 ← F1 ← F2 ← F3, all committed in the same second) tracking
+    /// `origin/main` (C1) and pushed to `fork/feature` (F1).
+    #[tokio::test]
+    async fn test_native_triangular() {
+        // F1 walked first, before its descendants
+        let ids = [[0x33; 20], [0x44; 20], [0x11; 20], [0x22; 20]];
+        let hex = ids.map(|id| to_hex(&id));
+        let objects = [
+            commit(ids[0], &[], 1000),
+            commit(ids[1], &[ids[0]], 1000),
+            commit(ids[2], &[ids[1]], 1000),
+            commit(ids[3], &[ids[2]], 1000),
+        ];
+        let config = "[remote \"origin\"]\n\turl = /srv/origin.git\n\
+            \tfetch = +refs/heads/*:refs/remotes/origin/*\n\
+            [remote \"fork\"]\n\turl = /srv/fork.git\n\
+            \tfetch = +refs/heads/*:refs/remotes/fork/*\n\
+            [branch \"feature\"]\n\tremote = origin\n\tmerge = refs/heads/main\n";
+        let refs = [
+            format!("{}\n", hex[3]),
+            format!("{}\n", hex[0]),
+            format!("{}\n", hex[1]),
+        ];
+        let mut files: Vec<(&str, &[u8])> = vec![
+            (".git/HEAD", b"ref: refs/heads/feature\n"),
+            (".git/config", config.as_bytes()),
+            // in another file of the configuration, as in the one of the user
+            (".git/config.worktree", b"[remote]\n\tpushDefault = fork\n"),
+            (".git/refs/heads/feature", refs[0].as_bytes()),
+            (".git/refs/remotes/origin/main", refs[1].as_bytes()),
+            (".git/refs/remotes/fork/feature", refs[2].as_bytes()),
+        ];
+        files.extend(
+            objects
+                .iter()
+                .map(|(path, data)| (path.as_str(), data.as_slice())),
+        );
+        let repo = temp_repo("git-native-triangular", &files);
+        let opts = Options {
+            base_branch: Some("origin/main".into()),
+            ..Default::default()
+        };
+
+        Fixture::new()
+            .scope(async {
+                let chunk = Git.divergence(&opts, &repo).await;
+                assert_eq!(parts(chunk), some(None, Some("↑3")));
+
+                let chunk = Git.push(&opts, &repo).await;
+                assert_eq!(parts(chunk), some(None, Some("⇡2")));
+
+                let chunk = Git.base(&opts, &repo).await;
+                assert_eq!(parts(chunk), some(Some("origin/main"), Some("↑3")));
+
+                // the upstream branch is deleted
+                std::fs::remove_file(repo.join(".git/refs/remotes/origin/main")).unwrap();
+                let chunk = Git.divergence(&opts, &repo).await;
+                assert_eq!(parts(chunk), some(None, Some("[gone]")));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_native_detached() {
+        let opts = Options::default();
+        let (repo, ids) = native_repo("git-native-detached");
+        std::fs::write(repo.join(".git/HEAD"), to_hex(&ids[0])).unwrap();
+
+        Fixture::new()
+            .scope(async {
+                let chunk = Git.branch(&opts, &repo).await;
+                assert_eq!(parts(chunk), some(Some("⚠"), None));
+
+                let chunk = Git.commit(&opts, &repo).await;
+                let expected = format!("v1∷{}", &to_hex(&ids[0])[..8]);
+                assert_eq!(parts(chunk), some(None, Some(&expected)));
+
+                assert!(Git.divergence(&opts, &repo).await.is_none());
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_native_worktree() {
+        let opts = Options::default();
+        let (repo, _) = native_repo("git-native-worktree");
+        let worktree = repo.join("linked");
+        let git_dir = repo.join(".git/worktrees/linked");
+        std::fs::create_dir_all(&git_dir).unwrap();
+        std::fs::create_dir_all(&worktree).unwrap();
+        std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/feature\n").unwrap();
+        std::fs::write(git_dir.join("commondir"), "../..\n").unwrap();
+        let gitdir = format!("gitdir: {}\n", git_dir.display());
+        std::fs::write(worktree.join(".git"), gitdir).unwrap();
+
+        Fixture::new()
+            .scope(async {
+                let chunk = Git.worktree(&opts, &worktree).await;
+                assert_eq!(parts(chunk), some(Some("⌂"), Some("[feature]")));
+
+                // an unborn branch
+                let chunk = Git.branch(&opts, &worktree).await;
+                assert_eq!(parts(chunk), some(None, Some("feature")));
+
+                // the stashes are shared by the worktrees
+                let chunk = Git.stash(&opts, &worktree).await;
+                assert_eq!(parts(chunk), some(None, Some("≡²")));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_native_fallback() {
+        // SHA-256 repositories are left to the CLI
+        let repo = temp_repo(
+            "git-native-sha256",
+            &[
+                (".git/HEAD", b"ref: refs/heads/main\n"),
+                (".git/config", b"[extensions]\n\tobjectFormat = sha256\n"),
+            ],
+        );
+        let fixture = Fixture::new().with(STATUS, &header("main"));
+        let chunk = fixture.scope(Git.branch(&Options::default(), &repo)).await;
+        assert_eq!(parts(chunk), some(Some("⎇"), Some("main")));
+    }
 }
@@ -0,0 +1,273 @@
+//! Walks of the commit graph: the divergence of two commits and the nearest tag.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use itertools::Itertools;
+use smallvec::SmallVec;
+use smol_str::{format_smolstr, SmolStr};
+
+use crate::providers::vcs::git::odb::{from_hex, to_hex, Kind, ObjectId};
+use crate::providers::vcs::git::repo::{Repository, Tag};
+use crate::providers::vcs::LastCommit;
+
+/// The commits walked at most, beyond which the CLI is asked instead.
+const MAX_WALK: usize = 100_000;
+
+#[derive(Debug, Clone)]
+pub struct Commit {
+    parents: SmallVec<[ObjectId; 2]>,
+    time: i64,
+}
+
+const LEFT: u8 = 1;
+const RIGHT: u8 = 2;
+const BOTH: u8 = LEFT | RIGHT;
+/// The commit is walked, its flags passed on to its parents.
+const WALKED: u8 = 4;
+
+/// The tags `git describe` finds before choosing the nearest (`--candidates`).
+const MAX_CANDIDATES: usize = 10;
+/// The commit is queued, the other flags of `describe` being those of the candidates
+/// it is reachable from.
+const QUEUED: u32 = 1;
+
+/// The commits to walk, newest first, and in the order they were queued among those of
+/// the same date (as `git describe` keeps them).
+#[derive(Default)]
+struct ByDate {
+    heap: BinaryHeap<(i64, Reverse<u64>, ObjectId)>,
+    queued: u64,
+}
+
+impl ByDate {
+    fn push(&mut self, time: i64, id: ObjectId) {
+        self.queued += 1;
+        self.heap.push((time, Reverse(self.queued), id));
+    }
+
+    fn pop(&mut self) -> Option<ObjectId> {
+        self.heap.pop().map(|(_, _, id)| id)
+    }
+}
+
+/// A tag `describe` may name the commit by, and the commits of the walk not reachable
+/// from it.
+struct Candidate<'a> {
+    tag: &'a Tag,
+    depth: u64,
+    flag: u32,
+}
+
+impl Repository {
+    fn commit(&self, id: &ObjectId) -> Option<Commit> {
+        if let Some(commit) = self.commits.lock().get(id) {
+            return Some(commit.clone());
+        }
+
+        let (kind, content) = self.odb.read(id)?;
+        if kind != Kind::Commit {
+            return None;
+        }
+        let mut parents = SmallVec::new();
+        let mut time = 0;
+        for line in content.split(|&b| b == b'\n') {
+            if line.is_empty() {
+                break; // the message
+            }
+            let line = std::str::from_utf8(line).ok()?;
+            if let Some(parent) = line.strip_prefix("parent ") {
+                parents.push(from_hex(parent)?);
+            } else if let Some(committer) = line.strip_prefix("committer ") {
+                // `name <email> timestamp timezone`
+                time = committer.rsplit(' ').nth(1)?.parse().ok()?;
+            }
+        }
+
+        let commit = Commit { parents, time };
+        self.commits.lock().insert(*id, commit.clone());
+        Some(commit)
+    }
+
+    /// The commit time, the author and the subject (`git log -1 --format=%ct%n%an%n%s`) of
+    /// a commit.
+    pub fn last_commit(&self, id: &ObjectId) -> Option<LastCommit> {
+        let (kind, content) = self.odb.read(id)?;
+        if kind != Kind::Commit {
+            return None;
+        }
+        let content = String::from_utf8_lossy(&content);
+        let (header, message) = content.split_once("\n\n").unwrap_or((&content, ""));
+        let mut commit = LastCommit::default();
+        for line in header.lines() {
+            if let Some(author) = line.strip_prefix("author ") {
+                commit.author = author.split(" <").next().unwrap_or_default().into();
+            } else if let Some(committer) = line.strip_prefix("committer ") {
+                commit.time = committer.rsplit(' ').nth(1)?.parse().ok()?;
+            }
+        }
+        // the first paragraph, on a single line
+        let paragraph = message
+            .trim_start()
+            .split("\n\n")
+            .next()
+            .unwrap_or_default();
+        commit.subject = paragraph.lines().map(str::trim).join(" ").into();
+        Some(commit)
+    }
+
+    /// The number of commits reachable from `left` but not from `right`, and vice versa
+    /// (`git rev-list --count right..left` and `left..right`).
+    ///
+    /// The commits are walked newest first and the walk stops when only common commits
+    /// are left. A commit whose flags grow after it was walked (e.g. a parent committed in
+    /// the same second as its child) is walked again, even when common, to pass them on to
+    /// its ancestors.
+    pub fn ahead_behind(&self, left: ObjectId, right: ObjectId) -> Option<(u64, u64)> {
+        if left == right {
+            return Some((0, 0));
+        }
+
+        let mut flags = HashMap::<ObjectId, u8>::new();
+        // the commits to walk, and whether they are walked again
+        let mut queue = BinaryHeap::new();
+        for (id, flag) in [(left, LEFT), (right, RIGHT)] {
+            flags.insert(id, flag);
+            queue.push((self.commit(&id)?.time, id, false));
+        }
+        // the entries of the queue walked again or not yet common
+        let mut pending = queue.len();
+
+        while pending > 0 {
+            let (_, id, again) = queue.pop()?;
+            let flag = flags[&id] & BOTH;
+            if again || flag != BOTH {
+                pending -= 1;
+            }
+            flags.insert(id, flag | WALKED);
+            if flags.len() > MAX_WALK {
+                return None;
+            }
+
+            for parent in self.commit(&id)?.parents {
+                let old = flags.get(&parent).copied();
+                let new = old.unwrap_or(0) & BOTH | flag;
+                match old {
+                    None => {
+                        queue.push((self.commit(&parent)?.time, parent, false));
+                        pending += usize::from(new != BOTH);
+                    }
+                    Some(old) if old & BOTH == new => continue,
+                    Some(old) if old & WALKED != 0 => {
+                        queue.push((self.commit(&parent)?.time, parent, true));
+                        pending += 1;
+                    }
+                    // queued, and now common
+                    Some(_) => pending -= usize::from(new == BOTH),
+                }
+                flags.insert(parent, new);
+            }
+        }
+
+        let count = |side| flags.values().filter(|&&f| f & BOTH == side).count() as u64;
+        Some((count(LEFT), count(RIGHT)))
+    }
+
+    /// The nearest tag of `head` in the format of
+    /// `git describe --abbrev=8 --always --tags --long`: `tag-N-gHASH`, or the
+    /// abbreviated hash without tags.
+    ///
+    /// As git does, the commits are walked newest first until ten tags are found, or
+    /// until the last commit left is reachable from the nearest ones, once an annotated tag
+    /// is found. Of those, the tag with the fewest commits walked not reachable from it is
+    /// chosen, and the walk goes on to count them all.
+    pub fn describe(&self, head: ObjectId) -> Option<SmolStr> {
+        let abbrev = &to_hex(&head)[..8];
+        let tags = self.tags();
+        if let Some(tag) = tags.get(&head) {
+            return Some(format_smolstr!("{}-0-g{abbrev}", tag.name));
+        }
+
+        let mut flags = HashMap::from([(head, QUEUED)]);
+        let mut queue = ByDate::default();
+        queue.push(self.commit(&head)?.time, head);
+        let mut candidates = SmallVec::<[Candidate; MAX_CANDIDATES]>::new();
+        let mut annotated = false;
+        let mut walked = 0;
+        while let Some(id) = queue.pop() {
+            walked += 1;
+            if let Some(tag) = tags.get(&id) {
+                if candidates.len() == MAX_CANDIDATES {
+                    // walked again, to count the commits of the nearest one
+                    queue.push(self.commit(&id)?.time, id);
+                    break;
+                }
+                let flag = 2 << candidates.len();
+                candidates.push(Candidate {
+                    tag,
+                    depth: walked - 1,
+                    flag,
+                });
+                *flags.get_mut(&id)? |= flag;
+                annotated |= tag.date.is_some();
+            }
+            let flag = flags[&id];
+            for candidate in &mut candidates {
+                candidate.depth += u64::from(flag & candidate.flag == 0);
+            }
+            // the last commit left reachable from all the nearest candidates
+            if annotated && queue.heap.is_empty() {
+                let nearest = candidates.iter().map(|c| c.depth).min().unwrap_or(0);
+                let within = candidates
+                    .iter()
+                    .filter(|c| c.depth == nearest)
+                    .fold(0, |within, c| within | c.flag);
+                if flag & within == within {
+                    break;
+                }
+            }
+            self.queue_parents(&id, &mut flags, &mut queue)?;
+        }
+
+        // the first found among the nearest ones
+        candidates.sort_by_key(|candidate| candidate.depth);
+        let Some(best) = candidates.first_mut() else {
+            return Some(abbrev.into());
+        };
+        while let Some(id) = queue.pop() {
+            if flags[&id] & best.flag == 0 {
+                best.depth += 1;
+            } else if queue
+                .heap
+                .iter()
+                .all(|(_, _, id)| flags[id] & best.flag != 0)
+            {
+                break;
+            }
+            self.queue_parents(&id, &mut flags, &mut queue)?;
+        }
+        Some(format_smolstr!(
+            "{}-{}-g{abbrev}",
+            best.tag.name,
+            best.depth
+        ))
+    }
+
+    /// Queue the parents of a commit that are not queued yet, passing its flags on to them.
+    fn queue_parents(
+        &self,
+        id: &ObjectId,
+        flags: &mut HashMap<ObjectId, u32>,
+        queue: &mut ByDate,
+    ) -> Option<()> {
+        let flag = flags[id];
+        for parent in self.commit(id)?.parents {
+            let old = flags.entry(parent).or_default();
+            if *old & QUEUED == 0 {
+                queue.push(self.commit(&parent)?.time, parent);
+            }
+            *old |= flag;
+        }
+        (flags.len() <= MAX_WALK).then_some(())
+    }
+}
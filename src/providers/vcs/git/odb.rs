@@ -0,0 +1,440 @@
+//! A read-only object database: loose objects and packfiles (with their `.idx` v2).
+
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+use miniz_oxide::inflate::{decompress_to_vec_zlib_with_limit, TINFLStatus};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+
+pub type ObjectId = [u8; 20];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+/// The objects of a repository (and of its alternates).
+pub struct Odb {
+    dirs: Vec<PathBuf>,
+    packs: Vec<Pack>,
+}
+
+struct Pack {
+    idx: File,
+    pack: File,
+    /// The length of the pack, which bounds the compressed data of its objects.
+    len: u64,
+    fanout: Box<[u32; 256]>,
+    /// The objects the deltas are applied to, by offset: the chains of deltas of
+    /// neighbouring commits share most of their bases.
+    bases: Mutex<HashMap<u64, (Kind, Vec<u8>)>>,
+}
+
+/// The bases of deltas kept in memory, and the largest one.
+const MAX_BASES: usize = 4096;
+const MAX_BASE_SIZE: usize = 64 * 1024;
+
+/// The maximum length of a chain of deltas, beyond which a pack is deemed corrupted.
+const MAX_DELTA_DEPTH: usize = 512;
+
+/// The largest object read, beyond which the size declared by a pack or a delta is
+/// deemed corrupted rather than allocated.
+const MAX_OBJECT_SIZE: usize = 256 * 1024 * 1024;
+
+/// The header of a loose object: its kind, its size in decimal and a NUL byte.
+const MAX_LOOSE_HEADER: usize = 32;
+
+impl Odb {
+    /// The object database in `objects`, along with its alternates.
+    pub fn open(objects: &Path) -> Self {
+        let mut dirs = vec![objects.to_path_buf()];
+        if let Ok(alternates) = std::fs::read_to_string(objects.join("info/alternates")) {
+            dirs.extend(
+                alternates
+                    .lines()
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(|l| objects.join(l)),
+            );
+        }
+
+        let packs = dirs
+            .iter()
+            .filter_map(|dir| std::fs::read_dir(dir.join("pack")).ok())
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                (path.extension()? == "idx")
+                    .then(|| Pack::open(&path))
+                    .flatten()
+            })
+            .collect();
+
+        Self { dirs, packs }
+    }
+
+    /// The kind and the content of the object.
+    pub fn read(&self, id: &ObjectId) -> Option<(Kind, Vec<u8>)> {
+        self.read_packed(id, 0).or_else(|| self.read_loose(id))
+    }
+
+    fn read_loose(&self, id: &ObjectId) -> Option<(Kind, Vec<u8>)> {
+        let hex = to_hex(id);
+        let data = self
+            .dirs
+            .iter()
+            .find_map(|dir| std::fs::read(dir.join(&hex[..2]).join(&hex[2..])).ok())?;
+
+        // the header (e.g. `commit 1234\0`) is inflated first, and the content then up to
+        // the size it declares
+        let header = match decompress_to_vec_zlib_with_limit(&data, MAX_LOOSE_HEADER) {
+            Ok(header) => header,
+            Err(e) if e.status == TINFLStatus::HasMoreOutput => e.output,
+            Err(_) => return None,
+        };
+        let nul = header.iter().position(|&b| b == 0)?;
+        let (kind, size) = std::str::from_utf8(&header[..nul]).ok()?.split_once(' ')?;
+        let size = size
+            .parse::<usize>()
+            .ok()
+            .filter(|&size| size <= MAX_OBJECT_SIZE)?;
+        let kind = match kind {
+            "commit" => Kind::Commit,
+            "tree" => Kind::Tree,
+            "blob" => Kind::Blob,
+            "tag" => Kind::Tag,
+            _ => return None,
+        };
+        let mut content =
+            decompress_to_vec_zlib_with_limit(&data, (nul + 1).checked_add(size)?).ok()?;
+        content.drain(..=nul);
+        (content.len() == size).then_some((kind, content))
+    }
+
+    fn read_packed(&self, id: &ObjectId, depth: usize) -> Option<(Kind, Vec<u8>)> {
+        self.packs.iter().find_map(|pack| {
+            let offset = pack.find(id)?;
+            pack.read_at(self, offset, depth)
+        })
+    }
+}
+
+impl Pack {
+    fn open(idx_path: &Path) -> Option<Self> {
+        let idx = File::open(idx_path).ok()?;
+        let pack = File::open(idx_path.with_extension("pack")).ok()?;
+        let len = pack.metadata().ok()?.len();
+
+        let mut header = [0u8; 8 + 256 * 4];
+        idx.read_exact_at(&mut header, 0).ok()?;
+        if header[..8] != [0xff, b't', b'O', b'c', 0, 0, 0, 2] {
+            return None;
+        }
+        let mut fanout = Box::new([0u32; 256]);
+        for (n, entry) in fanout.iter_mut().enumerate() {
+            *entry = u32::from_be_bytes(header[8 + n * 4..12 + n * 4].try_into().unwrap());
+        }
+        Some(Self {
+            idx,
+            pack,
+            len,
+            fanout,
+            bases: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn count(&self) -> u64 {
+        self.fanout[255] as u64
+    }
+
+    /// The offset of the object in the pack, by a binary search of the index.
+    fn find(&self, id: &ObjectId) -> Option<u64> {
+        const NAMES: u64 = 8 + 256 * 4;
+        let first = id[0] as usize;
+        let mut lo = if first == 0 {
+            0
+        } else {
+            self.fanout[first - 1]
+        } as u64;
+        let mut hi = self.fanout[first] as u64;
+
+        let mut name = [0u8; 20];
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            self.idx.read_exact_at(&mut name, NAMES + mid * 20).ok()?;
+            match name.cmp(id) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return self.offset(mid),
+            }
+        }
+        None
+    }
+
+    fn offset(&self, n: u64) -> Option<u64> {
+        let offsets = 8 + 256 * 4 + self.count() * 24;
+        let mut buf = [0u8; 8];
+        self.idx
+            .read_exact_at(&mut buf[..4], offsets + n * 4)
+            .ok()?;
+        let offset = u32::from_be_bytes(buf[..4].try_into().unwrap());
+        if offset & 0x8000_0000 == 0 {
+            return Some(offset as u64);
+        }
+        let large = offsets + self.count() * 4 + (offset & 0x7fff_ffff) as u64 * 8;
+        self.idx.read_exact_at(&mut buf, large).ok()?;
+        Some(u64::from_be_bytes(buf))
+    }
+
+    fn read_at(&self, odb: &Odb, offset: u64, depth: usize) -> Option<(Kind, Vec<u8>)> {
+        if depth > MAX_DELTA_DEPTH {
+            return None;
+        }
+        if let Some(base) = self.bases.lock().get(&offset) {
+            return Some(base.clone());
+        }
+        let object = self.read_uncached(odb, offset, depth)?;
+        if depth > 0 && object.1.len() <= MAX_BASE_SIZE {
+            let mut bases = self.bases.lock();
+            if bases.len() >= MAX_BASES {
+                bases.clear();
+            }
+            bases.insert(offset, object.clone());
+        }
+        Some(object)
+    }
+
+    fn read_uncached(&self, odb: &Odb, offset: u64, depth: usize) -> Option<(Kind, Vec<u8>)> {
+        // the header is followed by at most 20 bytes (the base of a delta), and by the
+        // compressed data, a little longer than the content in the worst case
+        let mut header = [0u8; 16 + 20];
+        let read = read_at_most(&self.pack, &mut header, offset)?;
+        let header = &header[..read];
+        let mut pos = 0;
+        let mut byte = *header.get(pos)?;
+        let kind = (byte >> 4) & 0x7;
+        let mut size = (byte & 0x0f) as u64;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            pos += 1;
+            byte = *header.get(pos)?;
+            if shift + 7 > u64::BITS {
+                return None;
+            }
+            size |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+        }
+        pos += 1;
+
+        let base = match kind {
+            6 => {
+                let mut byte = *header.get(pos)?;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    pos += 1;
+                    byte = *header.get(pos)?;
+                    if distance >= u64::MAX >> 7 {
+                        return None;
+                    }
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+                }
+                pos += 1;
+                Some(self.read_at(odb, offset.checked_sub(distance)?, depth + 1)?)
+            }
+            7 => {
+                let id: ObjectId = header.get(pos..pos + 20)?.try_into().ok()?;
+                pos += 20;
+                Some(
+                    odb.read_packed(&id, depth + 1)
+                        .or_else(|| odb.read_loose(&id))?,
+                )
+            }
+            _ => None,
+        };
+
+        let size = usize::try_from(size)
+            .ok()
+            .filter(|&size| size <= MAX_OBJECT_SIZE)?;
+        let start = offset.checked_add(pos as u64)?;
+        let available = usize::try_from(self.len.checked_sub(start)?).ok()?;
+        let bound = (size / 16000 * 5).checked_add(size)?.checked_add(64)?;
+        let mut compressed = vec![0u8; bound.min(available)];
+        let read = read_at_most(&self.pack, &mut compressed, start)?;
+        let data = decompress_to_vec_zlib_with_limit(&compressed[..read], size).ok()?;
+        if data.len() != size {
+            return None;
+        }
+
+        match (kind, base) {
+            (1, _) => Some((Kind::Commit, data)),
+            (2, _) => Some((Kind::Tree, data)),
+            (3, _) => Some((Kind::Blob, data)),
+            (4, _) => Some((Kind::Tag, data)),
+            (6 | 7, Some((kind, base))) => Some((kind, apply_delta(&base, &data)?)),
+            _ => None,
+        }
+    }
+}
+
+/// Read into `buf` as many bytes as available, up to its length.
+fn read_at_most(file: &File, buf: &mut [u8], offset: u64) -> Option<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read_at(&mut buf[read..], offset + read as u64) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(_) => return None,
+        }
+    }
+    Some(read)
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    fn varint(delta: &[u8], pos: &mut usize) -> Option<usize> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = *delta.get(*pos)?;
+            *pos += 1;
+            if shift + 7 > usize::BITS {
+                return None;
+            }
+            value |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+    }
+
+    let mut pos = 0;
+    if varint(delta, &mut pos)? != base.len() {
+        return None;
+    }
+    let size = varint(delta, &mut pos)?;
+    if size > MAX_OBJECT_SIZE {
+        return None;
+    }
+    // a few bytes of the delta may copy much of the base: the capacity is only a hint
+    let mut result = Vec::with_capacity(size.min(base.len().saturating_add(delta.len())));
+
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 != 0 {
+            // copy from the base
+            let mut offset = 0usize;
+            let mut len = 0usize;
+            for bit in 0..4 {
+                if op & (1 << bit) != 0 {
+                    offset |= (*delta.get(pos)? as usize) << (bit * 8);
+                    pos += 1;
+                }
+            }
+            for bit in 0..3 {
+                if op & (0x10 << bit) != 0 {
+                    len |= (*delta.get(pos)? as usize) << (bit * 8);
+                    pos += 1;
+                }
+            }
+            if len == 0 {
+                len = 0x10000;
+            }
+            result.extend_from_slice(base.get(offset..offset.checked_add(len)?)?);
+        } else if op != 0 {
+            // insert from the delta
+            result.extend_from_slice(delta.get(pos..pos.checked_add(op as usize)?)?);
+            pos += op as usize;
+        } else {
+            return None;
+        }
+        if result.len() > size {
+            return None;
+        }
+    }
+
+    (result.len() == size).then_some(result)
+}
+
+pub fn to_hex(id: &ObjectId) -> String {
+    use std::fmt::Write;
+    id.iter().fold(String::with_capacity(40), |mut hex, b| {
+        let _ = write!(hex, "{b:02x}");
+        hex
+    })
+}
+
+pub fn from_hex(hex: &str) -> Option<ObjectId> {
+    let hex = hex.as_bytes();
+    if hex.len() != 40 {
+        return None;
+    }
+    let mut id = [0u8; 20];
+    for (n, byte) in id.iter_mut().enumerate() {
+        let digit = |c: u8| (c as char).to_digit(16);
+        *byte = (digit(hex[2 * n])? * 16 + digit(hex[2 * n + 1])?) as u8;
+    }
+    Some(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::vcs::tests::temp_repo;
+
+    #[test]
+    fn test_apply_delta() {
+        let base = b"the quick brown fox";
+        // source and target sizes, copy 10 bytes at 0, insert "red", copy 4 bytes at 15
+        let mut delta = vec![19, 17, 0x90, 10, 3];
+        delta.extend_from_slice(b"red");
+        delta.extend_from_slice(&[0x91, 15, 4]);
+        assert_eq!(
+            apply_delta(base, &delta).as_deref(),
+            Some(&b"the quick red fox"[..])
+        );
+
+        // a base of the wrong size
+        assert_eq!(apply_delta(b"short", &delta), None);
+        // a copy out of the base
+        assert_eq!(apply_delta(base, &[19, 4, 0x91, 18, 4]), None);
+        // a size overflowing
+        assert_eq!(apply_delta(base, &[0xff; 16]), None);
+        // a target larger than any object, or than declared
+        assert_eq!(apply_delta(base, &[19, 0xff, 0xff, 0xff, 0xff, 0x0f]), None);
+        assert_eq!(apply_delta(base, &[19, 2, 0x90, 10]), None);
+    }
+
+    #[test]
+    fn test_corrupted_size() {
+        // a commit declaring some 2^57 bytes, and one of 64 MiB in a pack of a few bytes
+        let mut data = b"PACK\0\0\0\x02\0\0\0\x02".to_vec();
+        data.extend_from_slice(&[0x9f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x78]);
+        data.extend_from_slice(&[0x90, 0x80, 0x80, 0x80, 0x02, 0x78, 0x9c]);
+        let repo = temp_repo("odb-size", &[("pack", &data)]);
+        let pack = Pack {
+            idx: File::open(repo.join("pack")).unwrap(),
+            pack: File::open(repo.join("pack")).unwrap(),
+            len: data.len() as u64,
+            fanout: Box::new([0; 256]),
+            bases: Mutex::new(HashMap::new()),
+        };
+        let odb = Odb::open(&repo);
+        assert_eq!(pack.read_at(&odb, 12, 0), None);
+        assert_eq!(pack.read_at(&odb, 22, 0), None);
+    }
+
+    #[test]
+    fn test_hex() {
+        let id = from_hex("0123456789abcdef0123456789abcdef01234567").unwrap();
+        assert_eq!(id[..3], [0x01, 0x23, 0x45]);
+        assert_eq!(to_hex(&id), "0123456789abcdef0123456789abcdef01234567");
+        assert_eq!(from_hex("0123"), None);
+        assert_eq!(from_hex(&"zz".repeat(20)), None);
+    }
+}
@@ -0,0 +1,127 @@
+//! The output of `git status --porcelain=v2 --branch --show-stash`: the branch, its
+//! upstream, the divergence, the stash and the changed files in a single run of git.
+
+use smol_str::SmolStr;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Porcelain {
+    /// Whether HEAD points to a commit (not an unborn branch).
+    pub born: bool,
+    /// The name of the branch, `None` when HEAD is detached.
+    pub branch: Option<SmolStr>,
+    pub upstream: Option<SmolStr>,
+    /// The commits ahead and behind the upstream, when it exists.
+    pub divergence: Option<(u64, u64)>,
+    pub stash: u64,
+    /// The `XY` codes of the changed files, as in `git status --porcelain` (v1).
+    pub changes: Vec<SmolStr>,
+    /// The changed submodules.
+    pub submodules: Vec<Submodule>,
+}
+
+/// The state of a changed submodule (the `S<c><m><u>` field of its entry).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Submodule {
+    /// Its checked out commit differs from the recorded one.
+    pub commit: bool,
+    pub modified: bool,
+    pub untracked: bool,
+}
+
+impl Porcelain {
+    pub fn parse(output: &str) -> Self {
+        let mut status = Self::default();
+        for line in output.lines() {
+            if let Some(header) = line.strip_prefix("# ") {
+                let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+                match key {
+                    "branch.oid" => status.born = value != "(initial)",
+                    "branch.head" if value != "(detached)" => status.branch = Some(value.into()),
+                    "branch.upstream" => status.upstream = Some(value.into()),
+                    "branch.ab" => {
+                        let mut counts = value
+                            .split(' ')
+                            .map(|n| n.trim_start_matches(['+', '-']).parse().ok());
+                        if let (Some(Some(ahead)), Some(Some(behind))) =
+                            (counts.next(), counts.next())
+                        {
+                            status.divergence = Some((ahead, behind));
+                        }
+                    }
+                    "stash" => status.stash = value.parse().unwrap_or(0),
+                    _ => {}
+                }
+                continue;
+            }
+
+            let mut fields = line.splitn(4, ' ');
+            let code = match (fields.next(), fields.next()) {
+                // ordinary, renamed or copied, and unmerged entries
+                (Some("1" | "2" | "u"), Some(xy)) if xy.len() == 2 => {
+                    if let Some(sub) = fields.next().and_then(|s| s.strip_prefix('S')) {
+                        let flag = |i| sub.as_bytes().get(i) != Some(&b'.');
+                        status.submodules.push(Submodule {
+                            commit: flag(0),
+                            modified: flag(1),
+                            untracked: flag(2),
+                        });
+                    }
+                    xy.replace('.', " ").into()
+                }
+                (Some("?"), Some(_)) => "??".into(),
+                (Some("!"), Some(_)) => "!!".into(),
+                _ => continue,
+            };
+            status.changes.push(code);
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let status = Porcelain::parse(
+            "# branch.oid 1234567890abcdef1234567890abcdef12345678\n\
+             # branch.head main\n\
+             # branch.upstream origin/main\n\
+             # branch.ab +2 -3\n\
+             # stash 4\n\
+             1 M. N... 100644 100644 100644 aaaa bbbb staged.rs\n\
+             1 .M N... 100644 100644 100644 aaaa aaaa modified file.rs\n\
+             2 R. N... 100644 100644 100644 aaaa aaaa R100 new.rs\told.rs\n\
+             u UU N... 100644 100644 100644 100644 aaaa bbbb cccc conflict.rs\n\
+             1 .M S.MU 160000 160000 160000 aaaa aaaa vendor/lib\n\
+             ? untracked.txt\n",
+        );
+        assert_eq!(
+            status,
+            Porcelain {
+                born: true,
+                branch: Some("main".into()),
+                upstream: Some("origin/main".into()),
+                divergence: Some((2, 3)),
+                stash: 4,
+                changes: ["M ", " M", "R ", "UU", " M", "??"]
+                    .map(Into::into)
+                    .to_vec(),
+                submodules: vec![Submodule {
+                    commit: false,
+                    modified: true,
+                    untracked: true,
+                }],
+            }
+        );
+
+        // an unborn branch, and a detached HEAD
+        let status = Porcelain::parse("# branch.oid (initial)\n# branch.head main\n");
+        assert!(!status.born && status.branch.as_deref() == Some("main"));
+        let status = Porcelain::parse(
+            "# branch.oid 1234567890abcdef1234567890abcdef12345678\n# branch.head (detached)\n",
+        );
+        assert!(status.born && status.branch.is_none() && status.divergence.is_none());
+    }
+}
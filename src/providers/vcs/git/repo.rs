@@ -0,0 +1,412 @@
+//! The metadata of a repository read from the git directory: `HEAD`, the loose and packed
+//! refs, the configuration, the stash reflog and the linked worktrees.
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+use smol_str::{format_smolstr, SmolStr};
+
+use crate::cmd::git_dir;
+use crate::providers::vcs::git::graph::Commit;
+use crate::providers::vcs::git::odb::{from_hex, Kind, ObjectId, Odb};
+
+/// A repository, read in-process.
+pub struct Repository {
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+    config: HashMap<SmolStr, Vec<SmolStr>>,
+    pub(super) odb: Odb,
+    pub(super) commits: Mutex<HashMap<ObjectId, Commit>>,
+}
+
+/// A tag naming a commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: SmolStr,
+    /// The date of the tagger of an annotated tag, `None` for a lightweight one.
+    pub date: Option<i64>,
+}
+
+/// What `HEAD` points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Head {
+    /// A branch (e.g. `refs/heads/main`), possibly unborn.
+    Branch(SmolStr),
+    Detached(ObjectId),
+}
+
+impl Repository {
+    /// The repository of the working tree `root`, unless it uses a format that cannot be
+    /// read in-process (e.g. SHA-256 object names or the reftable ref storage).
+    pub fn open(root: &Path) -> Option<Self> {
+        let git_dir = git_dir(root)?;
+        let common_dir = common_dir(&git_dir);
+        let read = |path: &Path| {
+            std::fs::read_to_string(path)
+                .map(|config| parse_config(&config))
+                .unwrap_or_default()
+        };
+        let local = read(&common_dir.join("config"));
+
+        let unsupported = |key: &str, supported: &str| {
+            local
+                .get(key)
+                .and_then(|values| values.last())
+                .is_some_and(|v| !v.eq_ignore_ascii_case(supported))
+        };
+        if unsupported("extensions.objectformat", "sha1")
+            || unsupported("extensions.refstorage", "files")
+            || common_dir.join("reftable").exists()
+        {
+            return None;
+        }
+
+        // the values of the most specific files last, as git reads them
+        let mut config = HashMap::<_, Vec<_>>::new();
+        for path in config_files(&git_dir).iter().rev() {
+            for (key, values) in read(path) {
+                config.entry(key).or_default().extend(values);
+            }
+        }
+
+        Some(Self {
+            odb: Odb::open(&common_dir.join("objects")),
+            git_dir,
+            common_dir,
+            config,
+            commits: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// A value of the configuration (e.g. `branch.main.remote`), the section and the key
+    /// being case-insensitive; the last one of a multi-valued key.
+    pub fn config(&self, key: &str) -> Option<&str> {
+        self.config_all(key).last().map(SmolStr::as_str)
+    }
+
+    /// All the values of a multi-valued key of the configuration (e.g. `remote.origin.fetch`).
+    pub fn config_all(&self, key: &str) -> &[SmolStr] {
+        self.config_values(key).unwrap_or_default()
+    }
+
+    fn config_values(&self, key: &str) -> Option<&[SmolStr]> {
+        let (section, rest) = key.split_once('.')?;
+        let (subsection, name) = match rest.rsplit_once('.') {
+            Some((subsection, name)) => (Some(subsection), name),
+            None => (None, rest),
+        };
+        let key = match subsection {
+            Some(sub) => format_smolstr!(
+                "{}.{sub}.{}",
+                section.to_ascii_lowercase(),
+                name.to_ascii_lowercase()
+            ),
+            None => format_smolstr!(
+                "{}.{}",
+                section.to_ascii_lowercase(),
+                name.to_ascii_lowercase()
+            ),
+        };
+        self.config.get(&key).map(Vec::as_slice)
+    }
+
+    pub fn head(&self) -> Option<Head> {
+        let head = std::fs::read_to_string(self.git_dir.join("HEAD")).ok()?;
+        match head.trim().strip_prefix("ref:") {
+            Some(target) => Some(Head::Branch(target.trim().into())),
+            None => from_hex(head.trim()).map(Head::Detached),
+        }
+    }
+
+    /// The commit `HEAD` points to, if any.
+    pub fn head_commit(&self) -> Option<ObjectId> {
+        match self.head()? {
+            Head::Branch(branch) => self.resolve(&branch),
+            Head::Detached(id) => Some(id),
+        }
+    }
+
+    /// The path of a ref: the per-worktree ones are in the git directory, the others in
+    /// the common one.
+    fn ref_path(&self, name: &str) -> PathBuf {
+        let per_worktree = name == "HEAD"
+            || name.starts_with("refs/bisect/")
+            || name.starts_with("refs/worktree/")
+            || name.starts_with("refs/rewritten/");
+        if per_worktree {
+            self.git_dir.join(name)
+        } else {
+            self.common_dir.join(name)
+        }
+    }
+
+    /// The target of a symbolic ref (e.g. `refs/remotes/origin/HEAD`).
+    pub fn symbolic_target(&self, name: &str) -> Option<SmolStr> {
+        let content = std::fs::read_to_string(self.ref_path(name)).ok()?;
+        content
+            .trim()
+            .strip_prefix("ref:")
+            .map(|target| target.trim().into())
+    }
+
+    /// The object a ref points to, following the symbolic refs.
+    pub fn resolve(&self, name: &str) -> Option<ObjectId> {
+        let mut name = SmolStr::from(name);
+        for _ in 0..5 {
+            match std::fs::read_to_string(self.ref_path(&name)) {
+                Ok(content) => match content.trim().strip_prefix("ref:") {
+                    Some(target) => name = target.trim().into(),
+                    None => return from_hex(content.trim()),
+                },
+                Err(_) => return self.packed_refs().get(&name).copied(),
+            }
+        }
+        None
+    }
+
+    /// The refs of `packed-refs`, the peeled objects of the annotated tags (`^` lines)
+    /// being skipped.
+    fn packed_refs(&self) -> HashMap<SmolStr, ObjectId> {
+        let mut refs = HashMap::new();
+        let Ok(content) = std::fs::read_to_string(self.common_dir.join("packed-refs")) else {
+            return refs;
+        };
+        for line in content.lines() {
+            if let Some((id, name)) = line.split_once(' ') {
+                if let Some(id) = from_hex(id) {
+                    refs.insert(SmolStr::from(name.trim()), id);
+                }
+            }
+        }
+        refs
+    }
+
+    /// The tags by the commit they point to, the annotated ones being peeled. Of the tags
+    /// of a commit, `git describe` names it by the newest annotated one, or else by the
+    /// first one by name.
+    pub fn tags(&self) -> HashMap<ObjectId, Tag> {
+        let mut refs = BTreeMap::new();
+        for (name, id) in self.packed_refs() {
+            if let Some(name) = name.strip_prefix("refs/tags/") {
+                refs.insert(SmolStr::from(name), id);
+            }
+        }
+        let root = self.common_dir.join("refs/tags");
+        let mut dirs = vec![root.clone()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                let name = path.strip_prefix(&root).ok().and_then(Path::to_str);
+                let id = std::fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|content| from_hex(content.trim()));
+                if let (Some(name), Some(id)) = (name, id) {
+                    refs.insert(SmolStr::from(name), id);
+                }
+            }
+        }
+
+        let mut tags = HashMap::<_, Tag>::new();
+        for (name, id) in refs {
+            let Some((commit, date)) = self.peel(id) else {
+                continue;
+            };
+            match tags.get(&commit) {
+                Some(other) if other.date >= date => {}
+                _ => {
+                    tags.insert(commit, Tag { name, date });
+                }
+            }
+        }
+        tags
+    }
+
+    /// The commit a tag points to, following the annotated tags, and the date of the
+    /// tagger of the outermost one.
+    fn peel(&self, mut id: ObjectId) -> Option<(ObjectId, Option<i64>)> {
+        let mut date = None;
+        for _ in 0..5 {
+            match self.odb.read(&id)? {
+                (Kind::Commit, _) => return Some((id, date)),
+                (Kind::Tag, content) => {
+                    let content = String::from_utf8_lossy(&content);
+                    let header = content.split("\n\n").next().unwrap_or_default();
+                    let (mut object, mut time) = (None, None);
+                    for line in header.lines() {
+                        if let Some(hex) = line.strip_prefix("object ") {
+                            object = from_hex(hex.trim());
+                        } else if let Some(tagger) = line.strip_prefix("tagger ") {
+                            // `name <email> timestamp timezone`
+                            time = tagger.rsplit(' ').nth(1).and_then(|t| t.parse().ok());
+                        }
+                    }
+                    // a tag without a tagger is as old as can be
+                    date = date.or(Some(time.unwrap_or(0)));
+                    id = object?;
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// The number of stashed changes.
+    pub fn stash_count(&self) -> u64 {
+        std::fs::read_to_string(self.common_dir.join("logs/refs/stash"))
+            .map(|log| log.lines().filter(|l| !l.is_empty()).count() as u64)
+            .unwrap_or(0)
+    }
+
+    /// Whether the working tree is a linked worktree (rather than the main one).
+    pub fn is_linked_worktree(&self) -> bool {
+        self.git_dir != self.common_dir
+    }
+
+    /// The upstream ref of a branch (e.g. `refs/remotes/origin/main` for
+    /// `refs/heads/main`), as configured by `branch.<name>.remote` and `.merge`, mapped
+    /// by the fetch refspecs of the remote.
+    pub fn upstream(&self, branch: &str) -> Option<SmolStr> {
+        let name = branch.strip_prefix("refs/heads/")?;
+        let remote = self.config(&format!("branch.{name}.remote"))?;
+        let merge = self.config(&format!("branch.{name}.merge"))?;
+        self.remote_ref(remote, merge)
+    }
+
+    /// Whether an upstream is configured for a branch, existing or not.
+    pub fn tracks(&self, branch: &str) -> bool {
+        branch
+            .strip_prefix("refs/heads/")
+            .is_some_and(|name| self.config(&format!("branch.{name}.merge")).is_some())
+    }
+
+    /// The remote-tracking ref a branch is pushed to (`@{push}`), by the push remote of
+    /// the branch and `push.default`.
+    pub fn push_ref(&self, branch: &str) -> Option<SmolStr> {
+        let name = branch.strip_prefix("refs/heads/")?;
+        let remote = self
+            .config(&format!("branch.{name}.remote"))
+            .unwrap_or("origin");
+        let push_remote = self
+            .config(&format!("branch.{name}.pushremote"))
+            .or_else(|| self.config("remote.pushdefault"))
+            .unwrap_or(remote);
+        let triangular = remote != push_remote;
+        match self.config("push.default").unwrap_or("simple") {
+            "nothing" => None,
+            "upstream" | "tracking" if !triangular => self.upstream(branch),
+            "simple" if !triangular => {
+                let upstream = self.config(&format!("branch.{name}.merge"))?;
+                (upstream == branch).then(|| self.upstream(branch))?
+            }
+            "upstream" | "tracking" => None,
+            _ => self.remote_ref(push_remote, branch),
+        }
+    }
+
+    /// The local ref of the branch `name` of a remote, mapped by its fetch refspecs.
+    fn remote_ref(&self, remote: &str, name: &str) -> Option<SmolStr> {
+        if remote == "." {
+            return Some(name.into());
+        }
+        self.config_all(&format!("remote.{remote}.fetch"))
+            .iter()
+            .find_map(|refspec| map_refspec(refspec, name))
+    }
+
+    /// The commit of a branch, a remote-tracking branch or a tag given by its short name
+    /// (e.g. `origin/main`), or of a full ref.
+    pub fn resolve_short(&self, name: &str) -> Option<ObjectId> {
+        if name.starts_with("refs/") {
+            return self.resolve(name);
+        }
+        ["refs/heads/", "refs/remotes/", "refs/tags/"]
+            .iter()
+            .find_map(|prefix| self.resolve(&format!("{prefix}{name}")))
+    }
+}
+
+/// The destination of `name` by the refspec (e.g. `+refs/heads/*:refs/remotes/origin/*`).
+fn map_refspec(refspec: &str, name: &str) -> Option<SmolStr> {
+    let (src, dst) = refspec.trim_start_matches('+').split_once(':')?;
+    match (src.split_once('*'), dst.split_once('*')) {
+        (Some((src_prefix, src_suffix)), Some((dst_prefix, dst_suffix))) => {
+            let matched = name.strip_prefix(src_prefix)?.strip_suffix(src_suffix)?;
+            Some(format_smolstr!("{dst_prefix}{matched}{dst_suffix}"))
+        }
+        (None, None) => (src == name).then(|| dst.into()),
+        _ => None,
+    }
+}
+
+/// The directory shared by the worktrees of the repository (the git directory of the
+/// main worktree), with the configuration and the refs.
+pub(super) fn common_dir(git_dir: &Path) -> PathBuf {
+    std::fs::read_to_string(git_dir.join("commondir"))
+        .map(|common| git_dir.join(common.trim()))
+        .unwrap_or_else(|_| git_dir.to_path_buf())
+}
+
+/// The configuration files of the repository, from the most to the least specific: of the
+/// worktree, of the repository, of the user and of the system.
+pub(super) fn config_files(git_dir: &Path) -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let xdg = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".config")));
+    [
+        Some(git_dir.join("config.worktree")),
+        Some(common_dir(git_dir).join("config")),
+        home.map(|home| home.join(".gitconfig")),
+        xdg.map(|xdg| xdg.join("git/config")),
+        Some(PathBuf::from("/etc/gitconfig")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Parse a git configuration into `section.key` and `section.subsection.key` entries,
+/// the section and the key lowercased.
+pub(super) fn parse_config(content: &str) -> HashMap<SmolStr, Vec<SmolStr>> {
+    let mut config = HashMap::new();
+    let mut section = SmolStr::default();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let Some((header, _)) = header.split_once(']') else {
+                continue;
+            };
+            section = match header.split_once(char::is_whitespace) {
+                Some((name, sub)) => format_smolstr!(
+                    "{}.{}",
+                    name.to_ascii_lowercase(),
+                    sub.trim().trim_matches('"')
+                ),
+                None => header.to_ascii_lowercase().into(),
+            };
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (line, "true"),
+        };
+        let value = value
+            .split_once(" #")
+            .or_else(|| value.split_once(" ;"))
+            .map_or(value, |(value, _)| value.trim_end());
+        config
+            .entry(format_smolstr!("{section}.{}", key.to_ascii_lowercase()))
+            .or_insert_with(Vec::new)
+            .push(value.trim_matches('"').into());
+    }
+    config
+}
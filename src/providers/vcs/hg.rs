@@ -1,5 +1,9 @@
 use crate::cmd::CMD;
-use crate::providers::vcs::{merge_icons, StatusIcon, VcsTrait};
+use crate::providers::vcs::remote::Remote;
+use crate::providers::vcs::{
+    merge_icons, parse_diffstat, render_age, render_diff, LastCommit, StatusIcon, VcsTrait,
+};
+use crate::value::{LineCounts, StatusCounts, Value};
 use crate::{chunk::Chunk, options::Options};
 use smallvec::SmallVec;
 use smol_str::{format_smolstr, SmolStr, SmolStrBuilder, ToSmolStr};
@@ -27,6 +31,42 @@ impl VcsTrait for Hg {
         Some(Chunk::new("hg ⎇", branch.trim().to_smolstr()))
     }
 
+    async fn remote(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        // the `default` path of the `[paths]` section of `.hg/hgrc`
+        let hgrc = fs::read_to_string(path.join(".hg").join("hgrc"))
+            .await
+            .ok()?;
+        let mut section = "";
+        let url = hgrc.lines().map(str::trim).find_map(|line| {
+            if let Some(header) = line.strip_prefix('[') {
+                section = header.trim_end_matches(']').trim();
+                return None;
+            }
+            let (key, value) = line.split_once('=')?;
+            (section == "paths" && key.trim() == "default").then(|| value.trim())
+        })?;
+        Some(Remote::parse(url)?.chunk(opts))
+    }
+
+    async fn operation(&self, _opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let hg = path.join(".hg");
+        for (file, name) in [
+            ("histedit-state", "HISTEDIT"),
+            ("rebasestate", "REBASE"),
+            ("graftstate", "GRAFT"),
+            ("shelvedstate", "UNSHELVE"),
+            ("updatestate", "UPDATE"),
+            ("merge/state2", "MERGE"),
+            ("merge/state", "MERGE"),
+            ("bisect.state", "BISECT"),
+        ] {
+            if fs::try_exists(hg.join(file)).await.unwrap_or(false) {
+                return Some(Chunk::new("⧗", name.into()));
+            }
+        }
+        None
+    }
+
     async fn commit(&self, _opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
         // hg!("id").await.map(|s| Chunk::info(s.trim().to_smolstr()))
         let hash = get_hg_commit_hash(path).await?;
@@ -42,6 +82,36 @@ impl VcsTrait for Hg {
         }
     }
 
+    async fn age(&self, opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        let log = hg!(
+            "log",
+            "-r",
+            ".",
+            "-T",
+            "{date|hgdate}\n{author|person}\n{desc|firstline}"
+        )
+        .await?;
+        let mut lines = log.lines();
+        let commit = LastCommit {
+            time: lines.next()?.split(' ').next()?.parse().ok()?,
+            author: lines.next().unwrap_or_default().into(),
+            subject: lines.next().unwrap_or_default().into(),
+        };
+        // the null revision of an empty repository
+        if commit.time == 0 {
+            return None;
+        }
+        let user = match opts.commit_summary {
+            true => hg!("config", "ui.username").await,
+            false => None,
+        };
+        // the person of `Name <email>`
+        let user = user
+            .as_deref()
+            .map(|u| u.split(" <").next().unwrap_or(u).trim());
+        Some(render_age(opts, &commit, user))
+    }
+
     async fn status(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         // TODO: although hg is quite slow, it is very difficult to implement `hg status` without resorting to the `hg` command...
         hg!("status").await.map(|status| {
@@ -51,9 +121,23 @@ impl VcsTrait for Hg {
                     .map(|line| line.parse::<StatusIcon<Hg>>().unwrap())
                     .collect::<SmallVec<[_; 8]>>(),
             ))
+            .with_value(Value::Status(hg_status_counts(&status)))
         })
     }
 
+    async fn diff(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        let stat = hg!("diff", "--stat").await?;
+        render_diff(parse_diffstat(&stat), LineCounts::default())
+    }
+
+    async fn submodules(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn partial(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
     async fn worktree(&self, _opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
         let path = path.join(".hg").join("sharedpath");
         let sharedpath = fs::read_to_string(&path).await.ok()?;
@@ -69,6 +153,22 @@ impl VcsTrait for Hg {
     async fn divergence(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         None
     }
+
+    async fn push(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn base(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn fetch(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    fn fetch_command(&self) -> Option<&'static [&'static str]> {
+        Some(&["hg", "pull", "--quiet"])
+    }
 }
 
 impl FromStr for StatusIcon<Hg> {
@@ -88,6 +188,21 @@ impl FromStr for StatusIcon<Hg> {
     }
 }
 
+/// Count the files in each state from the output of `hg status`.
+fn hg_status_counts(status: &str) -> StatusCounts {
+    let mut counts = StatusCounts::default();
+    for line in status.lines() {
+        match line.chars().next() {
+            Some('A') => counts.added += 1,
+            Some('M') => counts.modified += 1,
+            Some('R' | '!') => counts.deleted += 1,
+            Some('?') => counts.untracked += 1,
+            _ => {}
+        }
+    }
+    counts
+}
+
 async fn get_hg_commit_hash(base: &Path) -> Option<SmolStr> {
     // Define the path to the dirstate file.
     let dirstate_path = base.join(".hg").join("dirstate");
@@ -116,3 +231,163 @@ async fn get_hg_commit_hash(base: &Path) -> Option<SmolStr> {
     // Return the resulting hexadecimal string.
     Some(hex_string.finish())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::fixture::Fixture;
+    use crate::providers::vcs::tests::{parts, temp_repo};
+
+    const DIRSTATE: &[u8] = &[
+        0x1f, 0x2e, 0x3d, 0x4c, 0x5b, 0x6a, 0x79, 0x88, 0x97, 0xa6, 0xb5, 0xc4, 0xd3, 0xe2, 0xf1,
+        0x00, 0x10, 0x20, 0x30, 0x40,
+    ];
+
+    #[tokio::test]
+    async fn test_age() {
+        let log = "hg log -r . -T {date|hgdate}\n{author|person}\n{desc|firstline}";
+        let fixture = Fixture::new()
+            .with(log, "1000 -3600\nBob\nbackout the fix")
+            .with("hg config ui.username", "Alice <alice@example.com>");
+        let opts = Options {
+            commit_summary: true,
+            ..Default::default()
+        };
+        let chunk = fixture.scope(Hg.age(&opts, Path::new("."))).await.unwrap();
+        assert!(chunk
+            .info_str()
+            .unwrap()
+            .ends_with("y backout the fix (Bob)"));
+
+        // an empty repository
+        let fixture = Fixture::new().with(log, "0 0\n\n");
+        assert!(fixture.scope(Hg.age(&opts, Path::new("."))).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_branch() {
+        let repo = temp_repo("hg-branch", &[(".hg/branch", b"stable\n")]);
+        let chunk = Hg.branch(&Options::default(), &repo).await;
+        assert_eq!(
+            parts(chunk),
+            Some((Some("hg ⎇".into()), Some("stable".into())))
+        );
+
+        let repo = temp_repo("hg-no-branch", &[(".hg/requires", b"store\n")]);
+        assert!(Hg.branch(&Options::default(), &repo).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_operation() {
+        let repo = temp_repo(
+            "hg-histedit",
+            &[(".hg/histedit-state", b"v1\n"), (".hg/merge/state2", b"")],
+        );
+        let chunk = Hg.operation(&Options::default(), &repo).await;
+        assert_eq!(
+            parts(chunk),
+            Some((Some("⧗".into()), Some("HISTEDIT".into())))
+        );
+
+        let repo = temp_repo("hg-merge", &[(".hg/merge/state", b"")]);
+        let chunk = Hg.operation(&Options::default(), &repo).await;
+        assert_eq!(parts(chunk), Some((Some("⧗".into()), Some("MERGE".into()))));
+
+        let repo = temp_repo("hg-no-operation", &[(".hg/requires", b"store\n")]);
+        assert!(Hg.operation(&Options::default(), &repo).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_commit() {
+        let repo = temp_repo("hg-commit", &[(".hg/dirstate", DIRSTATE)]);
+        let chunk = Hg.commit(&Options::default(), &repo).await;
+        assert_eq!(parts(chunk), Some((None, Some("1f2e3d4c5b6a7988+".into()))));
+
+        let repo = temp_repo(
+            "hg-bookmark",
+            &[
+                (".hg/dirstate", DIRSTATE),
+                (".hg/bookmarks.current", b"feature\n"),
+            ],
+        );
+        let chunk = Hg.commit(&Options::default(), &repo).await;
+        assert_eq!(
+            parts(chunk),
+            Some((None, Some("1f2e3d4c5b6a7988+ (feature)".into())))
+        );
+
+        // truncated dirstate
+        let repo = temp_repo("hg-truncated", &[(".hg/dirstate", &DIRSTATE[..10])]);
+        assert!(Hg.commit(&Options::default(), &repo).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_diff() {
+        let fixture = Fixture::new().with(
+            "hg diff --stat",
+            " src/main.rs |  7 +++++--\n 1 files changed, 5 insertions(+), 2 deletions(-)\n",
+        );
+        let chunk = fixture
+            .scope(Hg.diff(&Options::default(), Path::new(".")))
+            .await;
+        assert_eq!(parts(chunk), Some((None, Some("+5 -2".into()))));
+
+        let fixture = Fixture::new().with("hg diff --stat", "");
+        assert!(fixture
+            .scope(Hg.diff(&Options::default(), Path::new(".")))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let fixture = Fixture::new().with(
+            "hg status",
+            "M src/main.rs\nM src/lib.rs\nA new.rs\nR old.rs\n! missing.rs\n? notes.txt\n",
+        );
+        let chunk = fixture
+            .scope(Hg.status(&Options::default(), Path::new(".")))
+            .await
+            .unwrap();
+        assert_eq!(chunk.info_str(), Some("!?●²✖✚"));
+        assert_eq!(
+            chunk.value(),
+            Some(&Value::Status(StatusCounts {
+                added: 1,
+                modified: 2,
+                deleted: 2,
+                untracked: 1,
+                ..Default::default()
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_worktree() {
+        let repo = temp_repo("hg-share", &[(".hg/sharedpath", b"/srv/project/.hg")]);
+        let chunk = Hg.worktree(&Options::default(), &repo).await;
+        assert_eq!(
+            parts(chunk),
+            Some((Some("⌂".into()), Some("/srv/project".into())))
+        );
+    }
+
+    #[test]
+    fn test_status_icon() {
+        for (line, icon) in [
+            ("A a", "✚"),
+            ("M a", "●"),
+            ("R a", "✖"),
+            ("! a", "!"),
+            ("? a", "?"),
+            ("C a", ""),
+            ("I a", ""),
+        ] {
+            assert_eq!(
+                line.parse::<StatusIcon<Hg>>().unwrap().value,
+                icon,
+                "{line}"
+            );
+        }
+    }
+}
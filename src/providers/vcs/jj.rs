@@ -1,9 +1,14 @@
 use crate::cmd::CMD;
-use crate::providers::vcs::{merge_icons, StatusIcon, VcsTrait};
+use crate::providers::vcs::git::remote_url;
+use crate::providers::vcs::remote::Remote;
+use crate::providers::vcs::{
+    merge_icons, parse_diffstat, render_age, render_diff, LastCommit, StatusIcon, VcsTrait,
+};
+use crate::value::{LineCounts, StatusCounts, Value};
 use crate::{chunk::Chunk, options::Options};
 use smallvec::SmallVec;
 use smol_str::{format_smolstr, SmolStr};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 macro_rules! jj {
@@ -12,6 +17,12 @@ macro_rules! jj {
     };
 }
 
+/// The commit time, the author and the subject of a commit, one per line.
+const JJ_LAST_COMMIT: &str = concat!(
+    r#"committer.timestamp().format("%s") ++ "\n" ++ "#,
+    r#"author.name() ++ "\n" ++ description.first_line()"#
+);
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Jj;
 
@@ -33,6 +44,23 @@ impl VcsTrait for Jj {
             })
     }
 
+    async fn remote(&self, opts: &Options, path: &Path) -> Option<Chunk<SmolStr>> {
+        let url = remote_url(&jj_git_dir(path)?, None)?;
+        Some(Remote::parse(&url)?.chunk(opts))
+    }
+
+    async fn operation(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        // the conflicted paths follow the notice (e.g. `file    2-sided conflict`)
+        let status = jj!("status", "--color", "never", "--no-pager").await?;
+        let conflicts = status
+            .lines()
+            .skip_while(|l| !l.contains("unresolved conflicts at these paths"))
+            .skip(1)
+            .take_while(|l| l.contains("-sided conflict"))
+            .count();
+        (conflicts > 0).then(|| Chunk::new("⧗", format_smolstr!("CONFLICT {conflicts}")))
+    }
+
     async fn commit(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         let status = jj!("status", "--color", "never", "--no-pager").await?;
         let working_copy = status
@@ -56,6 +84,41 @@ impl VcsTrait for Jj {
         }
     }
 
+    async fn age(&self, opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        // the parent of the working-copy commit, rewritten at every snapshot: the snapshot
+        // leaves the parent unchanged, so it is skipped and the output can be cached
+        let log = jj!(
+            "log",
+            "--ignore-working-copy",
+            "--no-graph",
+            "--limit",
+            "1",
+            "-r",
+            "@-",
+            "--color",
+            "never",
+            "--no-pager",
+            "-T",
+            JJ_LAST_COMMIT
+        )
+        .await?;
+        let mut lines = log.lines();
+        let commit = LastCommit {
+            time: lines.next()?.parse().ok()?,
+            author: lines.next().unwrap_or_default().into(),
+            subject: lines.next().unwrap_or_default().into(),
+        };
+        // the root commit
+        if commit.time == 0 {
+            return None;
+        }
+        let user = match opts.commit_summary {
+            true => jj!("config", "get", "user.name").await,
+            false => None,
+        };
+        Some(render_age(opts, &commit, user.as_deref()))
+    }
+
     async fn status(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         jj!("status", "--color", "never", "--no-pager")
             .await
@@ -70,10 +133,24 @@ impl VcsTrait for Jj {
                 if icons.is_empty() {
                     return None;
                 }
-                Some(Chunk::info(icons))
+                Some(Chunk::info(icons).with_value(Value::Status(jj_status_counts(&status))))
             })
     }
 
+    async fn diff(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        // the changes of the working-copy commit
+        let stat = jj!("diff", "--stat", "--color", "never", "--no-pager").await?;
+        render_diff(parse_diffstat(&stat), LineCounts::default())
+    }
+
+    async fn submodules(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn partial(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
     async fn worktree(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         jj!("log", "--color", "never", "--no-pager")
             .await
@@ -95,6 +172,22 @@ impl VcsTrait for Jj {
     async fn divergence(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         None
     }
+
+    async fn push(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn base(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn fetch(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    fn fetch_command(&self) -> Option<&'static [&'static str]> {
+        Some(&["jj", "git", "fetch"])
+    }
 }
 
 impl FromStr for StatusIcon<Jj> {
@@ -111,3 +204,246 @@ impl FromStr for StatusIcon<Jj> {
         }
     }
 }
+
+/// Count the files in each state from the output of `jj status`.
+fn jj_status_counts(status: &str) -> StatusCounts {
+    let mut counts = StatusCounts::default();
+    for line in status.lines().filter(|l| l.get(1..2) == Some(" ")) {
+        match line.split_whitespace().next() {
+            Some("A") => counts.added += 1,
+            Some("M") => counts.modified += 1,
+            Some("D") => counts.deleted += 1,
+            Some("R") => counts.renamed += 1,
+            Some("C") => counts.copied += 1,
+            _ => {}
+        }
+    }
+    counts
+}
+
+/// The git repository backing the store of the workspace `root`: `.jj/repo/store/git`,
+/// or the `.git` of the workspace when colocated.
+fn jj_git_dir(root: &Path) -> Option<PathBuf> {
+    let dot_jj = root.join(".jj");
+    // the repository of a secondary workspace is shared with the main one
+    let repo = match std::fs::read_to_string(dot_jj.join("repo")) {
+        Ok(repo) => dot_jj.join(repo.trim()),
+        Err(_) => dot_jj.join("repo"),
+    };
+    let store = repo.join("store");
+    let target = std::fs::read_to_string(store.join("git_target")).ok()?;
+    Some(store.join(target.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::fixture::Fixture;
+    use crate::providers::vcs::tests::{parts, temp_repo};
+
+    const LOG: &str = "\
+@  kkmpptxz user@example.com 2024-05-01 10:00:00 default@ main* 1b2c3d4e
+│  (no description set)
+◆  qpvuntsm user@example.com 2024-04-30 09:00:00 main@origin 230dd059
+│  initial
+";
+
+    const STATUS: &str = "\
+Working copy changes:
+M src/main.rs
+A src/new.rs
+A src/other.rs
+D old.rs
+Working copy  (@) : kkmpptxz 1b2c3d4e (no description set)
+Parent commit (@-): qpvuntsm 230dd059 main | initial
+";
+
+    #[tokio::test]
+    async fn test_branch() {
+        let fixture = Fixture::new().with("jj log --color never --no-pager", LOG);
+        let chunk = fixture
+            .scope(Jj.branch(&Options::default(), Path::new(".")))
+            .await;
+        assert_eq!(
+            parts(chunk),
+            Some((Some("jj ⎇".into()), Some("main".into())))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commit() {
+        let fixture = Fixture::new().with("jj status --color never --no-pager", STATUS);
+        let chunk = fixture
+            .scope(Jj.commit(&Options::default(), Path::new(".")))
+            .await;
+        assert_eq!(
+            parts(chunk),
+            Some((Some("⭑".into()), Some("kkmpptxz 1b2c3d4e".into())))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let fixture = Fixture::new().with("jj status --color never --no-pager", STATUS);
+        let chunk = fixture
+            .scope(Jj.status(&Options::default(), Path::new(".")))
+            .await
+            .unwrap();
+        assert_eq!(chunk.info_str(), Some("−●✚²"));
+        assert_eq!(
+            chunk.value(),
+            Some(&Value::Status(StatusCounts {
+                added: 2,
+                modified: 1,
+                deleted: 1,
+                ..Default::default()
+            }))
+        );
+
+        // no changes
+        let fixture = Fixture::new().with(
+            "jj status --color never --no-pager",
+            "The working copy has no changes.\nWorking copy  (@) : kkmpptxz 1b2c3d4e (empty)\n",
+        );
+        assert!(fixture
+            .scope(Jj.status(&Options::default(), Path::new(".")))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_operation() {
+        let status = "\
+Working copy  (@) : kkmpptxz 1b2c3d4e (conflict) (no description set)
+Parent commit (@-): qpvuntsm 230dd059 main | initial
+Warning: There are unresolved conflicts at these paths:
+src/main.rs    2-sided conflict
+src/lib.rs    2-sided conflict including 1 deletion
+Hint: Use `jj resolve --list` to see details about the conflicts.
+";
+        let fixture = Fixture::new().with("jj status --color never --no-pager", status);
+        let chunk = fixture
+            .scope(Jj.operation(&Options::default(), Path::new(".")))
+            .await;
+        assert_eq!(
+            parts(chunk),
+            Some((Some("⧗".into()), Some("CONFLICT 2".into())))
+        );
+
+        let fixture = Fixture::new().with("jj status --color never --no-pager", STATUS);
+        assert!(fixture
+            .scope(Jj.operation(&Options::default(), Path::new(".")))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_age() {
+        let cmd = format!(
+            "jj log --ignore-working-copy --no-graph --limit 1 -r @- --color never --no-pager \
+            -T {JJ_LAST_COMMIT}"
+        );
+        let fixture = Fixture::new()
+            .with(&cmd, "1000\nalice\nAdd the parser")
+            .with("jj config get user.name", "alice");
+        let opts = Options {
+            commit_summary: true,
+            ..Default::default()
+        };
+        let chunk = fixture.scope(Jj.age(&opts, Path::new("."))).await.unwrap();
+        assert!(chunk.info_str().unwrap().ends_with("y Add the parser"));
+
+        // the root commit
+        let fixture = Fixture::new().with(&cmd, "0\n\n");
+        assert!(fixture.scope(Jj.age(&opts, Path::new("."))).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_diff() {
+        let fixture = Fixture::new().with(
+            "jj diff --stat --color never --no-pager",
+            "src/main.rs | 3 ++-\nsrc/new.rs  | 4 ++++\n2 files changed, 6 insertions(+), 1 deletion(-)\n",
+        );
+        let chunk = fixture
+            .scope(Jj.diff(&Options::default(), Path::new(".")))
+            .await
+            .unwrap();
+        assert_eq!(chunk.info_str(), Some("+6 -1"));
+        let value = Value::Diff {
+            unstaged: LineCounts {
+                insertions: 6,
+                deletions: 1,
+            },
+            staged: LineCounts::default(),
+        };
+        assert_eq!(chunk.value(), Some(&value));
+    }
+
+    #[tokio::test]
+    async fn test_worktree() {
+        let fixture = Fixture::new().with("jj log --color never --no-pager", LOG);
+        let chunk = fixture
+            .scope(Jj.worktree(&Options::default(), Path::new(".")))
+            .await;
+        assert_eq!(
+            parts(chunk),
+            Some((Some("¶".into()), Some("default@".into())))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remote() {
+        let opts = Options::default();
+        let config = b"[remote \"origin\"]\n\turl = git@github.com:me/repo.git\n";
+
+        // the git repository of the store
+        let repo = temp_repo(
+            "jj-remote",
+            &[
+                (".jj/repo/store/git_target", b"git"),
+                (".jj/repo/store/git/config", config),
+            ],
+        );
+        let chunk = Jj.remote(&opts, &repo).await;
+        assert_eq!(
+            parts(chunk),
+            Some((Some("github".into()), Some("me/repo".into())))
+        );
+
+        // colocated with git
+        let repo = temp_repo(
+            "jj-remote-colocated",
+            &[
+                (".jj/repo/store/git_target", b"../../../.git"),
+                (".git/config", config),
+            ],
+        );
+        let chunk = Jj.remote(&opts, &repo).await;
+        assert_eq!(
+            parts(chunk),
+            Some((Some("github".into()), Some("me/repo".into())))
+        );
+
+        // without remotes
+        let repo = temp_repo("jj-remote-none", &[(".jj/repo/store/git_target", b"git")]);
+        assert!(Jj.remote(&opts, &repo).await.is_none());
+    }
+
+    #[test]
+    fn test_status_icon() {
+        for (line, icon) in [
+            ("A a", "✚"),
+            ("R {a => b}", "→"),
+            ("M a", "●"),
+            ("C {a => b}", "⊂"),
+            ("D a", "−"),
+            ("Working copy changes:", ""),
+        ] {
+            assert_eq!(
+                line.parse::<StatusIcon<Jj>>().unwrap().value,
+                icon,
+                "{line}"
+            );
+        }
+    }
+}
@@ -2,7 +2,7 @@ use crate::cmd::CMD;
 use std::path::Path;
 use std::str::FromStr;
 
-use crate::providers::vcs::{merge_icons, StatusIcon, VcsTrait};
+use crate::providers::vcs::{merge_icons, render_age, LastCommit, StatusIcon, VcsTrait};
 use crate::{chunk::Chunk, options::Options};
 use smallvec::SmallVec;
 use smol_str::{SmolStr, ToSmolStr};
@@ -25,6 +25,14 @@ impl VcsTrait for Pijul {
             .map(|s| Chunk::new("pijul ⎇", s[1..].trim().to_smolstr()))
     }
 
+    async fn remote(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn operation(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
     async fn commit(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         let output = pijul!("log", "--limit", "1").await?;
         let change = output.lines().next()?;
@@ -32,6 +40,22 @@ impl VcsTrait for Pijul {
         Some(Chunk::new("⭑", change_id.to_smolstr()))
     }
 
+    async fn age(&self, opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        let output = pijul!("log", "--limit", "1").await?;
+        let mut commit = LastCommit::default();
+        let mut lines = output.lines();
+        for line in lines.by_ref().take_while(|l| !l.is_empty()) {
+            if let Some(author) = line.strip_prefix("Author:") {
+                commit.author = author.trim().into();
+            } else if let Some(date) = line.strip_prefix("Date:") {
+                commit.time = parse_utc(date.trim())?;
+            }
+        }
+        commit.subject = lines.next().unwrap_or_default().trim().into();
+        // the current identity is not known: the author is always shown
+        (commit.time != 0).then(|| render_age(opts, &commit, None))
+    }
+
     async fn status(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         pijul!("diff", "--short")
             .await
@@ -45,6 +69,18 @@ impl VcsTrait for Pijul {
             })
     }
 
+    async fn diff(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn submodules(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn partial(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
     async fn worktree(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         None
     }
@@ -56,6 +92,22 @@ impl VcsTrait for Pijul {
     async fn divergence(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
         None
     }
+
+    async fn push(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn base(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    async fn fetch(&self, _opts: &Options, _path: &Path) -> Option<Chunk<SmolStr>> {
+        None
+    }
+
+    fn fetch_command(&self) -> Option<&'static [&'static str]> {
+        None
+    }
 }
 
 impl FromStr for StatusIcon<Pijul> {
@@ -76,3 +128,129 @@ impl FromStr for StatusIcon<Pijul> {
         }
     }
 }
+
+/// The seconds since the epoch of a UTC date (`2024-05-01 12:34:56.789 UTC`).
+fn parse_utc(date: &str) -> Option<i64> {
+    let mut fields = date
+        .split(['-', ' ', ':', '.', 'T'])
+        .map(|f| f.parse::<i64>().ok());
+    let mut next = || fields.next().flatten();
+    let (y, m, d) = (next()?, next()?, next()?);
+    let (hh, mm, ss) = (next()?, next()?, next()?);
+
+    // the days since the epoch of the proleptic Gregorian calendar
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + d - 1;
+    let days = era * 146097 + yoe * 365 + yoe / 4 - yoe / 100 + doy - 719468;
+    Some(days * 86400 + hh * 3600 + mm * 60 + ss)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::fixture::Fixture;
+    use crate::providers::vcs::tests::parts;
+
+    #[test]
+    fn test_parse_utc() {
+        assert_eq!(parse_utc("1970-01-01 00:00:00 UTC"), Some(0));
+        assert_eq!(
+            parse_utc("2024-02-29 12:34:56.123456789 UTC"),
+            Some(1709210096)
+        );
+        assert_eq!(parse_utc("yesterday"), None);
+    }
+
+    #[tokio::test]
+    async fn test_age() {
+        let log = "Change MNYNGT2VGEQZX4QA43FWBDVYQY7CGPN4SMY4YB4TJ4FRV4JN2RCQC\n\
+            Author: alice\n\
+            Date: 2024-02-29 12:34:56.123456789 UTC\n\n    \
+            Add the parser\n";
+        let fixture = Fixture::new().with("pijul log --limit 1", log);
+        let opts = Options {
+            commit_summary: true,
+            ..Default::default()
+        };
+        let chunk = fixture
+            .scope(Pijul.age(&opts, Path::new(".")))
+            .await
+            .unwrap();
+        assert!(chunk
+            .info_str()
+            .unwrap()
+            .ends_with(" Add the parser (alice)"));
+    }
+
+    #[tokio::test]
+    async fn test_branch() {
+        let fixture = Fixture::new().with("pijul channel", "  dev\n* main\n");
+        let chunk = fixture
+            .scope(Pijul.branch(&Options::default(), Path::new(".")))
+            .await;
+        assert_eq!(
+            parts(chunk),
+            Some((Some("pijul ⎇".into()), Some("main".into())))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commit() {
+        let fixture = Fixture::new().with(
+            "pijul log --limit 1",
+            "Change MNYNGT2VGEQZX4QA43FWBDVYQY7CGPN4SKAYBYTYMQ4FDHDT3ZXQC\nAuthor: user\nDate: 2024-05-01\n\n    initial\n",
+        );
+        let chunk = fixture
+            .scope(Pijul.commit(&Options::default(), Path::new(".")))
+            .await;
+        assert_eq!(
+            parts(chunk),
+            Some((
+                Some("⭑".into()),
+                Some("MNYNGT2VGEQZX4QA43FWBDVYQY7CGPN4SKAYBYTYMQ4FDHDT3ZXQC".into())
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let fixture = Fixture::new().with(
+            "pijul diff --short",
+            "M  src/lib.rs\nM  src/main.rs\nA  new.rs\nD  old.rs\n",
+        );
+        let chunk = fixture
+            .scope(Pijul.status(&Options::default(), Path::new(".")))
+            .await;
+        assert_eq!(parts(chunk), Some((None, Some("−●²✚".into()))));
+
+        let fixture = Fixture::new().with("pijul diff --short", "");
+        assert!(fixture
+            .scope(Pijul.status(&Options::default(), Path::new(".")))
+            .await
+            .is_none());
+    }
+
+    #[test]
+    fn test_status_icon() {
+        for (line, icon) in [
+            ("MV a", "→"),
+            ("D a", "−"),
+            ("UD a", "⊖"),
+            ("A a", "✚"),
+            ("SC a", "⚠"),
+            ("UC a", "!"),
+            ("M a", "●"),
+            ("R a", "◉"),
+            ("RZ a", "↺"),
+            ("", ""),
+        ] {
+            assert_eq!(
+                line.parse::<StatusIcon<Pijul>>().unwrap().value,
+                icon,
+                "{line}"
+            );
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! The hosting service of a repository, from the URL of its remote: its icon, the
+//! `owner/repo` slug and the web page of the repository.
+
+use smol_str::{format_smolstr, SmolStr};
+
+use crate::chunk::Chunk;
+use crate::options::Options;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Forge {
+    GitHub,
+    GitLab,
+    Bitbucket,
+    Codeberg,
+    SourceHut,
+    Gitea,
+    SelfHosted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    pub forge: Forge,
+    pub host: SmolStr,
+    /// The port of the web server, kept from the `http(s)` URLs only: the port of an `ssh`
+    /// URL is not that of the web page.
+    pub port: Option<u16>,
+    /// The path of the repository on the host, e.g. `owner/repo` or `group/sub/repo`.
+    pub slug: SmolStr,
+}
+
+impl Remote {
+    /// The remote of a URL (`https://host/owner/repo.git`, `ssh://git@host:22/owner/repo`,
+    /// `git@host:owner/repo.git`, ...), `None` for a local repository.
+    pub fn parse(url: &str) -> Option<Self> {
+        let (scheme, (authority, path)) = match url.split_once("://") {
+            Some(("file", _)) => return None,
+            Some((scheme, rest)) => (Some(scheme), rest.split_once('/')?),
+            // the scp-like syntax, `[user@]host:path`
+            None => (
+                None,
+                url.split_once(':')
+                    .filter(|(host, _)| !host.contains('/'))?,
+            ),
+        };
+        let host = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        let (host, port) = host.split_once(':').unwrap_or((host, ""));
+        let host = host.to_ascii_lowercase();
+        let port = match scheme {
+            Some("http" | "https") => port.parse().ok(),
+            _ => None,
+        };
+        let slug = path.trim_matches('/');
+        let slug = slug
+            .strip_suffix(".git")
+            .unwrap_or(slug)
+            .trim_end_matches('/');
+        if host.is_empty() || slug.is_empty() {
+            return None;
+        }
+
+        let forge = match host.as_str() {
+            "codeberg.org" => Forge::Codeberg,
+            h if h == "sr.ht" || h.ends_with(".sr.ht") => Forge::SourceHut,
+            h if h.contains("github") => Forge::GitHub,
+            h if h.contains("gitlab") => Forge::GitLab,
+            h if h.contains("bitbucket") => Forge::Bitbucket,
+            h if h.contains("gitea") => Forge::Gitea,
+            _ => Forge::SelfHosted,
+        };
+        Some(Self {
+            forge,
+            host: host.into(),
+            port,
+            slug: slug.into(),
+        })
+    }
+
+    /// The icon of the forge; without Nerd Fonts, its name (the host when self-hosted).
+    pub fn icon(&self, nerd_font: bool) -> SmolStr {
+        let (name, icon) = match self.forge {
+            Forge::GitHub => ("github", "\u{f09b}"),
+            Forge::GitLab => ("gitlab", "\u{f296}"),
+            Forge::Bitbucket => ("bitbucket", "\u{f171}"),
+            Forge::Codeberg => ("codeberg", "\u{e702}"),
+            Forge::SourceHut => ("sourcehut", "\u{f10c}"),
+            Forge::Gitea => ("gitea", "\u{f0d9a}"),
+            Forge::SelfHosted => (self.host.as_str(), "\u{f233}"),
+        };
+        SmolStr::new(if nerd_font { icon } else { name })
+    }
+
+    /// The segment of the remote: the icon of the forge and the slug, linked to the web
+    /// page of the repository with `--hyperlinks`.
+    pub fn chunk(&self, opts: &Options) -> Chunk<SmolStr> {
+        let chunk = Chunk::new(self.icon(opts.nerd_font), self.slug.clone());
+        match opts.hyperlinks {
+            true => chunk.with_link(self.url()),
+            false => chunk,
+        }
+    }
+
+    /// The web page of the repository.
+    pub fn url(&self) -> SmolStr {
+        match self.port {
+            Some(port) => format_smolstr!("https://{}:{port}/{}", self.host, self.slug),
+            None => format_smolstr!("https://{}/{}", self.host, self.slug),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        for (url, forge, host, slug) in [
+            (
+                "https://github.com/awgn/auraline.git",
+                Forge::GitHub,
+                "github.com",
+                "awgn/auraline",
+            ),
+            (
+                "git@gitlab.com:group/sub/repo.git",
+                Forge::GitLab,
+                "gitlab.com",
+                "group/sub/repo",
+            ),
+            (
+                "ssh://git@bitbucket.org:22/team/repo",
+                Forge::Bitbucket,
+                "bitbucket.org",
+                "team/repo",
+            ),
+            (
+                "https://codeberg.org/forgejo/forgejo/",
+                Forge::Codeberg,
+                "codeberg.org",
+                "forgejo/forgejo",
+            ),
+            (
+                "https://git.sr.ht/~sircmpwn/scdoc",
+                Forge::SourceHut,
+                "git.sr.ht",
+                "~sircmpwn/scdoc",
+            ),
+            (
+                "git@gitea.example.com:me/repo.git",
+                Forge::Gitea,
+                "gitea.example.com",
+                "me/repo",
+            ),
+            (
+                "https://user@Git.Example.com/srv/repo.git",
+                Forge::SelfHosted,
+                "git.example.com",
+                "srv/repo",
+            ),
+        ] {
+            let remote = Remote::parse(url).unwrap();
+            assert_eq!(
+                (remote.forge, remote.host.as_str(), remote.slug.as_str()),
+                (forge, host, slug),
+                "{url}"
+            );
+        }
+
+        for url in ["/srv/git/repo.git", "../repo", "file:///srv/git/repo.git"] {
+            assert_eq!(Remote::parse(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_icon_and_url() {
+        let remote = Remote::parse("git@github.com:awgn/auraline.git").unwrap();
+        assert_eq!(remote.icon(false), "github");
+        assert_eq!(remote.icon(true), "\u{f09b}");
+        assert_eq!(remote.url(), "https://github.com/awgn/auraline");
+
+        let remote = Remote::parse("ssh://git.example.com/repo").unwrap();
+        assert_eq!(remote.icon(false), "git.example.com");
+
+        // the port of the web server is kept, not that of ssh
+        let remote = Remote::parse("https://git.example.com:8443/srv/repo.git").unwrap();
+        assert_eq!(remote.url(), "https://git.example.com:8443/srv/repo");
+        let remote = Remote::parse("ssh://git@git.example.com:2222/srv/repo.git").unwrap();
+        assert_eq!(remote.url(), "https://git.example.com/srv/repo");
+    }
+}
@@ -74,3 +74,35 @@ pub async fn show(opts: &Options) -> Option<Chunk<SmolStr>> {
         })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::fixture::Fixture;
+
+    async fn virt(output: Option<&str>, nerd_font: bool) -> Option<SmolStr> {
+        let opts = Options {
+            virt: true,
+            nerd_font,
+            ..Default::default()
+        };
+        let fixture = match output {
+            Some(output) => Fixture::new().with("systemd-detect-virt", output),
+            None => Fixture::new(),
+        };
+        fixture
+            .scope(show(&opts))
+            .await
+            .map(|c| c.info_str().unwrap().into())
+    }
+
+    #[tokio::test]
+    async fn test_virt() {
+        assert_eq!(virt(Some("kvm"), false).await.as_deref(), Some("kvm"));
+        assert_eq!(virt(Some("kvm"), true).await.as_deref(), Some("󱗃"));
+        assert_eq!(virt(Some("none"), false).await, None);
+        assert_eq!(virt(Some("unknown"), false).await, None);
+        // bare metal: `systemd-detect-virt` fails
+        assert_eq!(virt(None, false).await, None);
+    }
+}
@@ -1,37 +1,61 @@
-use owo_colors::Style;
+use owo_colors::{AnsiColors, DynColors, Style};
 use smallvec::SmallVec;
 use smol_str::{SmolStr, SmolStrBuilder};
+use unicode_width::UnicodeWidthChar;
 
 pub fn build_color_style(theme: Option<&str>) -> Style {
-    if let Some(theme) = theme {
-        if let Some(tuple) = parse_true_color(theme) {
-            Style::new().truecolor(tuple.0, tuple.1, tuple.2)
-        } else {
-            match theme {
-                "black" => Style::new().black(),
-                "red" => Style::new().red(),
-                "green" => Style::new().green(),
-                "yellow" => Style::new().yellow(),
-                "blue" => Style::new().blue(),
-                "magenta" => Style::new().magenta(),
-                "cyan" => Style::new().cyan(),
-                "white" => Style::new().white(),
-                "purple" => Style::new().purple(),
-                "bright_black" => Style::new().bright_black(),
-                "bright_red" => Style::new().bright_red(),
-                "bright_green" => Style::new().bright_green(),
-                "bright_yellow" => Style::new().bright_yellow(),
-                "bright_purple" => Style::new().bright_purple(),
-                "bright_blue" => Style::new().bright_blue(),
-                "bright_magenta" => Style::new().bright_magenta(),
-                "bright_cyan" => Style::new().bright_cyan(),
-                "bright_white" => Style::new().bright_white(),
-                _ => Style::new(),
-            }
-        }
-    } else {
-        Style::new()
+    theme
+        .and_then(parse_color)
+        .map(|color| Style::new().color(color))
+        .unwrap_or_default()
+}
+
+/// Parse a style specification made of whitespace separated tokens, e.g. `"bold cyan"`
+/// or `"dimmed 255,128,0"`. The `theme` token stands for the current theme color.
+pub fn parse_style(spec: &str, theme: Option<&str>) -> Style {
+    spec.split_whitespace()
+        .fold(Style::new(), |style, token| match token {
+            "bold" => style.bold(),
+            "dimmed" => style.dimmed(),
+            "italic" => style.italic(),
+            "underline" => style.underline(),
+            "theme" => match theme.and_then(parse_color) {
+                Some(color) => style.color(color),
+                None => style,
+            },
+            color => match parse_color(color) {
+                Some(color) => style.color(color),
+                None => style,
+            },
+        })
+}
+
+fn parse_color(name: &str) -> Option<DynColors> {
+    if let Some((r, g, b)) = parse_true_color(name) {
+        return Some(DynColors::Rgb(r, g, b));
     }
+
+    let color = match name {
+        "black" => AnsiColors::Black,
+        "red" => AnsiColors::Red,
+        "green" => AnsiColors::Green,
+        "yellow" => AnsiColors::Yellow,
+        "blue" => AnsiColors::Blue,
+        "magenta" | "purple" => AnsiColors::Magenta,
+        "cyan" => AnsiColors::Cyan,
+        "white" => AnsiColors::White,
+        "bright_black" => AnsiColors::BrightBlack,
+        "bright_red" => AnsiColors::BrightRed,
+        "bright_green" => AnsiColors::BrightGreen,
+        "bright_yellow" => AnsiColors::BrightYellow,
+        "bright_blue" => AnsiColors::BrightBlue,
+        "bright_magenta" | "bright_purple" => AnsiColors::BrightMagenta,
+        "bright_cyan" => AnsiColors::BrightCyan,
+        "bright_white" => AnsiColors::BrightWhite,
+        _ => return None,
+    };
+
+    Some(DynColors::Ansi(color))
 }
 
 fn parse_true_color(input: &str) -> Option<(u8, u8, u8)> {
@@ -55,3 +79,73 @@ pub fn to_superscript(s: &str) -> SmolStr {
     }
     builder.finish()
 }
+
+/// The width of `s` on the terminal, ignoring the ANSI escape sequences.
+pub fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        let len = match escape_len(rest) {
+            Some(len) => len,
+            None => {
+                width += c.width().unwrap_or(0);
+                c.len_utf8()
+            }
+        };
+        rest = &rest[len..];
+    }
+    width
+}
+
+/// Wrap every run of ANSI escape sequences of `s` between `open` and `close`, the markers
+/// a shell uses to delimit the non-printing characters of the prompt.
+pub fn wrap_escapes(s: &str, open: &str, close: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('\x1b') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        let mut len = 0;
+        while let Some(n) = escape_len(&rest[len..]) {
+            len += n;
+        }
+
+        if len == 0 {
+            // an incomplete sequence is left as it is
+            out.push('\x1b');
+            rest = &rest[1..];
+            continue;
+        }
+
+        out.push_str(open);
+        out.push_str(&rest[..len]);
+        out.push_str(close);
+        rest = &rest[len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The length of the escape sequence at the beginning of `s`: a CSI sequence (such as
+/// the SGR colors), an OSC sequence terminated by BEL or ST, or a two-byte escape.
+fn escape_len(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    if bytes.first() != Some(&0x1b) {
+        return None;
+    }
+
+    match bytes.get(1)? {
+        b'[' => bytes[2..]
+            .iter()
+            .position(|b| (0x40..=0x7e).contains(b))
+            .map(|pos| pos + 3),
+        b']' => bytes[2..].iter().enumerate().find_map(|(i, b)| match b {
+            0x07 => Some(i + 3),
+            0x1b if bytes.get(i + 3) == Some(&b'\\') => Some(i + 4),
+            _ => None,
+        }),
+        b if b.is_ascii() => Some(2),
+        _ => None,
+    }
+}
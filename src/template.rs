@@ -0,0 +1,148 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use smallvec::SmallVec;
+use smol_str::{SmolStr, SmolStrBuilder};
+
+use crate::options::parse_segment;
+
+/// A prompt template, such as `"{user} {pwd}[ on {vcs.branch}]\n{exit_code}❯ "`.
+///
+/// - `{name}` is replaced by the segment `name`; a group of segments (e.g. `{vcs}`)
+///   is replaced by its segments separated by a space.
+/// - `[...]` is printed only if at least one of the segments it contains is not empty.
+/// - `\n`, `\t` and `\\`, `\{`, `\}`, `\[`, `\]` are escape sequences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(Vec<Token>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Text(SmolStr),
+    Segment(SmolStr),
+    Optional(Vec<Token>),
+}
+
+impl Template {
+    /// The names of the segments (or groups of segments) used by the template.
+    pub fn segments(&self) -> SmallVec<[&str; 16]> {
+        fn collect<'a>(tokens: &'a [Token], names: &mut SmallVec<[&'a str; 16]>) {
+            for token in tokens {
+                match token {
+                    Token::Text(_) => {}
+                    Token::Segment(name) => names.push(name),
+                    Token::Optional(inner) => collect(inner, names),
+                }
+            }
+        }
+
+        let mut names = SmallVec::new();
+        collect(&self.0, &mut names);
+        names
+    }
+
+    /// Render the template; `segment` returns the rendering of a segment (or group),
+    /// if any.
+    pub fn render<F, S>(&self, segment: F) -> String
+    where
+        F: Fn(&str) -> Option<S>,
+        S: std::fmt::Display,
+    {
+        let mut out = String::new();
+        render_tokens(&self.0, &segment, &mut out);
+        out
+    }
+}
+
+/// Render the tokens into `out`, returning whether any segment was printed.
+fn render_tokens<F, S>(tokens: &[Token], segment: &F, out: &mut String) -> bool
+where
+    F: Fn(&str) -> Option<S>,
+    S: std::fmt::Display,
+{
+    let mut printed = false;
+    for token in tokens {
+        match token {
+            Token::Text(text) => out.push_str(text),
+            Token::Segment(name) => {
+                if let Some(s) = segment(name) {
+                    let _ = write!(out, "{s}");
+                    printed = true;
+                }
+            }
+            Token::Optional(inner) => {
+                let mut buffer = String::new();
+                if render_tokens(inner, segment, &mut buffer) {
+                    out.push_str(&buffer);
+                    printed = true;
+                }
+            }
+        }
+    }
+    printed
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let tokens = parse_tokens(&mut chars, false)?;
+        Ok(Template(tokens))
+    }
+}
+
+fn parse_tokens(chars: &mut std::str::Chars, nested: bool) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut text = SmolStrBuilder::new();
+
+    macro_rules! flush {
+        () => {{
+            let t = std::mem::replace(&mut text, SmolStrBuilder::new()).finish();
+            if !t.is_empty() {
+                tokens.push(Token::Text(t));
+            }
+        }};
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some(c @ ('\\' | '{' | '}' | '[' | ']')) => text.push(c),
+                Some(c) => return Err(format!("invalid escape sequence '\\{c}' in template")),
+                None => return Err("trailing '\\' in template".into()),
+            },
+            '{' => {
+                flush!();
+                let mut name = SmolStrBuilder::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err("unclosed '{' in template".into()),
+                    }
+                }
+                tokens.push(Token::Segment(parse_segment(&name.finish())?));
+            }
+            '[' => {
+                flush!();
+                tokens.push(Token::Optional(parse_tokens(chars, true)?));
+            }
+            ']' if nested => {
+                flush!();
+                return Ok(tokens);
+            }
+            ']' => return Err("unbalanced ']' in template".into()),
+            '}' => return Err("unbalanced '}' in template".into()),
+            c => text.push(c),
+        }
+    }
+
+    if nested {
+        return Err("unclosed '[' in template".into());
+    }
+
+    flush!();
+    Ok(tokens)
+}
@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Serialize, Serializer};
+
+/// The typed value of a chunk, carried alongside the rendered info, so that the JSON
+/// output and the styling rules do not need to parse it back.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum Value {
+    Integer(i64),
+    Count(u64),
+    Percent(f64),
+    #[serde(serialize_with = "serialize_secs")]
+    Duration(Duration),
+    Path(PathBuf),
+    Divergence {
+        ahead: u64,
+        behind: u64,
+    },
+    Status(StatusCounts),
+    Submodules(SubmoduleCounts),
+    Diff {
+        unstaged: LineCounts,
+        staged: LineCounts,
+    },
+}
+
+/// The number of files of the working copy in each state. A file may be counted in
+/// more than one state (e.g. staged and modified).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct StatusCounts {
+    pub staged: u64,
+    pub added: u64,
+    pub modified: u64,
+    pub deleted: u64,
+    pub renamed: u64,
+    pub copied: u64,
+    pub untracked: u64,
+    pub conflicted: u64,
+}
+
+/// The number of submodules in each state. A submodule may be counted in more than one
+/// state (e.g. at another commit and dirty).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct SubmoduleCounts {
+    pub uninitialized: u64,
+    pub changed: u64,
+    pub dirty: u64,
+}
+
+/// The number of lines inserted and deleted by a set of changes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineCounts {
+    pub insertions: u64,
+    pub deletions: u64,
+}
+
+fn serialize_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
@@ -0,0 +1,56 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use std::ffi::OsString;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A repository whose `git` command is a shell script, in the directory `dir` of the
+/// temporary directory of the tests.
+pub struct FakeGit {
+    pub dir: PathBuf,
+    /// An empty repository (a `.git` directory without `HEAD`).
+    pub repo: PathBuf,
+    /// The directory of the fake `git`, to be put first in `PATH` with [`path_with`].
+    pub bin: PathBuf,
+}
+
+impl FakeGit {
+    pub fn new(name: &str, script: &str) -> Self {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        let (repo, bin) = (dir.join("repo"), dir.join("bin"));
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(&bin).unwrap();
+
+        let git = bin.join("git");
+        std::fs::write(&git, format!("#!/bin/sh\n{script}")).unwrap();
+        std::fs::set_permissions(&git, std::fs::Permissions::from_mode(0o755)).unwrap();
+        Self { dir, repo, bin }
+    }
+
+    /// `PATH` with the fake `git` first.
+    pub fn path(&self) -> OsString {
+        path_with(&self.bin)
+    }
+}
+
+/// Run `git` in `cwd` as a test user, asserting that it succeeds; its trimmed output.
+pub fn git(cwd: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(cwd)
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .output()
+        .expect("Failed to execute git");
+    assert!(output.status.success(), "git {args:?}");
+    String::from_utf8_lossy(&output.stdout).trim().to_owned()
+}
+
+/// `PATH` with `dir` first.
+pub fn path_with(dir: &Path) -> OsString {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::join_paths(std::iter::once(dir.to_path_buf()).chain(std::env::split_paths(&path)))
+        .unwrap()
+}
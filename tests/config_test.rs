@@ -0,0 +1,198 @@
+mod common;
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use common::FakeGit;
+
+fn get_auraline_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_auraline"))
+}
+
+fn configs_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("configs")
+}
+
+fn run_with_config(config: &str, args: &[&str]) -> Output {
+    let dir = configs_dir();
+    Command::new(get_auraline_bin())
+        .current_dir(&dir)
+        .env("AURALINE_CONFIG", dir.join(config))
+        .env_remove("AURALINE_PROFILE")
+        .env_remove("AURALINE_OPTIONS")
+        .env_remove("AURALINE_THEME")
+        .arg("prompt")
+        .args(args)
+        .output()
+        .expect("Failed to execute auraline")
+}
+
+#[test]
+fn test_config_segments_order() {
+    let output = run_with_config("order.toml", &["--exit-code", "3"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let pwd = configs_dir().canonicalize().unwrap();
+
+    let exit_code = stdout.find('✘').expect("exit code not shown");
+    let full_pwd = stdout
+        .find(pwd.to_str().unwrap())
+        .expect("full_pwd not shown");
+    assert!(exit_code < full_pwd, "unexpected order:\n{stdout}");
+}
+
+#[test]
+fn test_config_segment_style() {
+    let output = run_with_config("style.toml", &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let pwd = configs_dir().canonicalize().unwrap();
+
+    let expected = format!("\x1b[4m{}\x1b[0m", pwd.display());
+    assert!(stdout.contains(&expected), "unexpected style:\n{stdout:?}");
+}
+
+#[test]
+fn test_config_unknown_segment() {
+    let output = run_with_config("unknown.toml", &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("unknown segment 'weather'"), "{stderr}");
+}
+
+#[test]
+fn test_config_unknown_key() {
+    let output = run_with_config("typo.toml", &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("nerd_fonts"), "{stderr}");
+}
+
+#[test]
+fn test_config_missing_file() {
+    let output = run_with_config("missing.toml", &[]);
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_config_segments_override_profile() {
+    // the lean profile also shows the user, the hostname, the memory, ...
+    let output = run_with_config("profile.toml", &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let pwd = configs_dir().canonicalize().unwrap();
+
+    assert!(stdout.contains(pwd.to_str().unwrap()), "{stdout}");
+    assert!(!stdout.contains('%') && !stdout.contains('@'), "{stdout}");
+
+    // the segments enabled on the command line are kept
+    let output = run_with_config("profile.toml", &["--memory"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains('%'), "{stdout}");
+}
+
+#[test]
+fn test_config_vcs_subsegments() {
+    let repo = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("config_subsegments");
+    let _ = std::fs::remove_dir_all(&repo);
+    std::fs::create_dir_all(&repo).unwrap();
+    for args in [
+        &["init", "--quiet"][..],
+        &["checkout", "--quiet", "-b", "main"],
+        &["remote", "add", "origin", "https://github.com/me/repo"],
+        &["commit", "--quiet", "--allow-empty", "-m", "one"],
+    ] {
+        let status = Command::new("git")
+            .current_dir(&repo)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .status()
+            .expect("Failed to execute git");
+        assert!(status.success());
+    }
+    std::fs::write(repo.join("a.txt"), "a").unwrap();
+    std::fs::write(repo.join("b.txt"), "b\n").unwrap();
+    std::fs::write(repo.join(".git/FETCH_HEAD"), "").unwrap();
+    let status = Command::new("git")
+        .current_dir(&repo)
+        .args(["add", "b.txt"])
+        .status()
+        .expect("Failed to execute git");
+    assert!(status.success());
+    let config = repo.join(".git/auraline.toml");
+    std::fs::write(&config, "segments = [\"vcs.branch\"]\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(get_auraline_bin())
+            .current_dir(&repo)
+            .env("AURALINE_CONFIG", &config)
+            .env_remove("AURALINE_PROFILE")
+            .env_remove("AURALINE_OPTIONS")
+            .arg("prompt")
+            .args(args)
+            .output()
+            .expect("Failed to execute auraline");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    // the listed segments of a group are selected among the group...
+    let stdout = run(&[]);
+    assert!(stdout.contains("main") && !stdout.contains('⁇'), "{stdout}");
+
+    // ... unless the group is enabled on the command line
+    let stdout = run(&["--vcs"]);
+    assert!(stdout.contains("main") && stdout.contains('⁇'), "{stdout}");
+
+    // nor are the other segments of the group rendered in the right prompt
+    let stdout = run(&["--right", "vcs.status", "--rprompt"]);
+    assert!(!stdout.contains("main") && stdout.contains('⁇'), "{stdout}");
+
+    // the opt-in segments of a group are shown only when listed
+    let opt_in = ["◷", "●+1", "⟳", "↛", "me/repo"];
+    let stdout = run(&["--vcs"]);
+    assert!(stdout.contains("main"), "{stdout}");
+    assert!(!opt_in.iter().any(|info| stdout.contains(info)), "{stdout}");
+    let segments = [
+        "vcs.branch",
+        "vcs.age",
+        "vcs.diff",
+        "vcs.fetch",
+        "vcs.push",
+        "vcs.remote",
+    ];
+    std::fs::write(&config, format!("segments = {segments:?}\n")).unwrap();
+    let stdout = run(&[]);
+    assert!(opt_in.iter().all(|info| stdout.contains(info)), "{stdout}");
+}
+
+#[test]
+fn test_config_timeouts() {
+    let git = FakeGit::new("config_timeouts", "sleep 1\n");
+    let run = |args: &[&str]| {
+        let output = Command::new(get_auraline_bin())
+            .current_dir(&git.repo)
+            .env("AURALINE_CONFIG", configs_dir().join("timeout.toml"))
+            .env("PATH", git.path())
+            .env_remove("AURALINE_PROFILE")
+            .env_remove("AURALINE_OPTIONS")
+            .arg("prompt")
+            .args(args)
+            .output()
+            .expect("Failed to execute auraline");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    let stdout = run(&["--timings"]);
+
+    // only the branch has a budget shorter than the fake git command
+    assert_eq!(stdout.matches("(timeout)").count(), 1, "{stdout}");
+    assert!(stdout.contains("::branch"), "{stdout}");
+    let branch = stdout.lines().find(|l| l.contains("::branch")).unwrap();
+    assert!(branch.ends_with("(timeout)"), "{stdout}");
+
+    // the placeholder is shown in place of the branch
+    let stdout = run(&[]);
+    assert_eq!(stdout.matches('…').count(), 1, "{stdout}");
+}
@@ -0,0 +1,251 @@
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use common::FakeGit;
+
+fn get_auraline_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_auraline"))
+}
+
+/// A directory with a fake repository, a fake `git` logging its invocations and the
+/// runtime directory of the daemon.
+struct Fixture {
+    git: FakeGit,
+    repo: PathBuf,
+    log: PathBuf,
+    runtime: PathBuf,
+}
+
+impl Fixture {
+    fn new(name: &str) -> Self {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+        let log = dir.join("git.log");
+        let script = format!(
+            "echo \"$@\" >> '{}'\n\
+             case \"$1\" in describe) echo \"v1.0$AURALINE_TEST\";; esac\n",
+            log.display()
+        );
+        let git = FakeGit::new(name, &script);
+        let fixture = Fixture {
+            repo: git.repo.clone(),
+            runtime: git.dir.join("runtime"),
+            log,
+            git,
+        };
+
+        std::fs::write(fixture.repo.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::create_dir(&fixture.runtime).unwrap();
+        set_mode(&fixture.runtime, 0o700);
+        fixture
+    }
+
+    fn socket(&self) -> PathBuf {
+        self.runtime.join("auraline.sock")
+    }
+
+    fn start_daemon(&self) -> Daemon {
+        let child = Command::new(get_auraline_bin())
+            .env("XDG_RUNTIME_DIR", &self.runtime)
+            .env("PATH", self.git.path())
+            .arg("daemon")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to execute auraline daemon");
+        let daemon = Daemon(child);
+
+        let begin = Instant::now();
+        while !self.socket().exists() {
+            assert!(
+                begin.elapsed() < Duration::from_secs(5),
+                "daemon not started"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        daemon
+    }
+
+    /// Render the commit from the client, with a `PATH` without the fake `git`, running
+    /// `git` rather than reading the repository in-process.
+    fn client_commit(&self) -> String {
+        self.client("{vcs.commit}")
+    }
+
+    fn client(&self, template: &str) -> String {
+        let output = Command::new(get_auraline_bin())
+            .current_dir(&self.repo)
+            .env("XDG_RUNTIME_DIR", &self.runtime)
+            .env("XDG_CONFIG_HOME", &self.runtime)
+            .env_remove("AURALINE_CONFIG")
+            .env_remove("AURALINE_PROFILE")
+            .env_remove("AURALINE_OPTIONS")
+            .args([
+                "prompt",
+                "--client",
+                "--git-backend",
+                "cli",
+                "--template",
+                template,
+            ])
+            .output()
+            .expect("Failed to execute auraline");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    fn git_calls(&self) -> usize {
+        std::fs::read_to_string(&self.log)
+            .map(|log| log.lines().count())
+            .unwrap_or(0)
+    }
+}
+
+/// The daemon, killed when dropped.
+struct Daemon(Child);
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn set_mode(path: &Path, mode: u32) {
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+}
+
+fn touch(path: &Path) {
+    let file = std::fs::File::options().append(true).open(path).unwrap();
+    file.set_modified(std::time::SystemTime::now() + Duration::from_secs(1))
+        .unwrap();
+}
+
+#[test]
+fn test_daemon_cache() {
+    let fixture = Fixture::new("daemon_cache");
+    let _daemon = fixture.start_daemon();
+
+    assert!(fixture.client_commit().contains("v1.0"));
+    let calls = fixture.git_calls();
+    assert!(calls > 0);
+
+    // the outputs are served from the cache
+    assert!(fixture.client_commit().contains("v1.0"));
+    assert_eq!(fixture.git_calls(), calls);
+
+    // until the state of the repository changes
+    touch(&fixture.repo.join(".git/HEAD"));
+    assert!(fixture.client_commit().contains("v1.0"));
+    assert!(fixture.git_calls() > calls);
+
+    // the status of the working tree is never asked to the daemon
+    let log = std::fs::read_to_string(&fixture.log).unwrap();
+    assert!(log.contains("describe") && !log.contains("status"), "{log}");
+}
+
+#[test]
+fn test_daemon_namespaces() {
+    let fixture = Fixture::new("daemon_namespaces");
+    for (cmd, output) in [("ip", "blue"), ("systemd-detect-virt", "docker")] {
+        let path = fixture.git.bin.join(cmd);
+        let script = format!(
+            "#!/bin/sh\necho {cmd} \"$@\" >> '{}'\necho {output}\n",
+            fixture.log.display()
+        );
+        std::fs::write(&path, script).unwrap();
+        set_mode(&path, 0o755);
+    }
+    let _daemon = fixture.start_daemon();
+
+    let output = fixture.client("{netns} {virt}");
+    assert!(
+        output.contains("blue") && output.contains("docker"),
+        "{output}"
+    );
+    let calls = fixture.git_calls();
+
+    // the namespace of the client is identified, rather than that of the daemon
+    let log = std::fs::read_to_string(&fixture.log).unwrap();
+    let ip = log.lines().find(|l| l.starts_with("ip ")).unwrap();
+    let pid = ip.strip_prefix("ip netns identify ").unwrap();
+    assert!(pid.parse::<u32>().is_ok(), "{log}");
+
+    // and the outputs are served from the cache to the clients of the same namespaces
+    let output = fixture.client("{netns} {virt}");
+    assert!(
+        output.contains("blue") && output.contains("docker"),
+        "{output}"
+    );
+    assert_eq!(fixture.git_calls(), calls);
+}
+
+#[test]
+fn test_daemon_client_policy() {
+    let fixture = Fixture::new("daemon_policy");
+    let _daemon = fixture.start_daemon();
+    assert!(fixture.client_commit().contains("v1.0"));
+
+    // the commands run in the environment of the client
+    let config = fixture.runtime.join("auraline/config.toml");
+    std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+    std::fs::write(&config, "[exec]\nenv = { AURALINE_TEST = \"+client\" }\n").unwrap();
+    let output = fixture.client_commit();
+    assert!(output.contains("v1.0+client"), "{output}");
+}
+
+#[test]
+fn test_daemon_absent() {
+    let fixture = Fixture::new("daemon_absent");
+
+    // the commands are run in-process, by the real git
+    let output = fixture.client_commit();
+    assert!(!output.contains("v1.0"), "{output}");
+    assert_eq!(fixture.git_calls(), 0);
+
+    // a stale socket is ignored as well
+    let daemon = fixture.start_daemon();
+    drop(daemon);
+    assert!(fixture.socket().exists());
+    fixture.client_commit();
+    assert_eq!(fixture.git_calls(), 0);
+}
+
+#[test]
+fn test_daemon_already_running() {
+    let fixture = Fixture::new("daemon_running");
+    let _daemon = fixture.start_daemon();
+
+    let output = Command::new(get_auraline_bin())
+        .env("XDG_RUNTIME_DIR", &fixture.runtime)
+        .arg("daemon")
+        .output()
+        .expect("Failed to execute auraline daemon");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("already listening"), "{stderr}");
+}
+
+#[test]
+fn test_daemon_shared_runtime_dir() {
+    let fixture = Fixture::new("daemon_shared");
+    let daemon = fixture.start_daemon();
+
+    // another user could replace the socket of a directory they can write to
+    set_mode(&fixture.runtime, 0o777);
+    let output = fixture.client_commit();
+    assert!(!output.contains("v1.0"), "{output}");
+    assert_eq!(fixture.git_calls(), 0);
+    drop(daemon);
+
+    let output = Command::new(get_auraline_bin())
+        .env("XDG_RUNTIME_DIR", &fixture.runtime)
+        .arg("daemon")
+        .output()
+        .expect("Failed to execute auraline daemon");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("accessible to other users"), "{stderr}");
+}
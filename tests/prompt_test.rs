@@ -0,0 +1,717 @@
+mod common;
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use common::{git, FakeGit};
+
+fn get_auraline_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_auraline"))
+}
+
+fn tests_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+fn prompt_command() -> Command {
+    // no config file is found in the tests directory
+    let mut command = Command::new(get_auraline_bin());
+    command
+        .current_dir(tests_dir())
+        .env("XDG_CONFIG_HOME", tests_dir())
+        .env_remove("AURALINE_CONFIG")
+        .env_remove("AURALINE_PROFILE")
+        .env_remove("AURALINE_OPTIONS")
+        .env_remove("AURALINE_THEME")
+        .env_remove("SSH_CONNECTION")
+        .env_remove("COLUMNS")
+        .arg("prompt");
+    command
+}
+
+fn run_prompt(args: &[&str]) -> Output {
+    prompt_command()
+        .args(args)
+        .output()
+        .expect("Failed to execute auraline")
+}
+
+fn position(stdout: &str, pattern: &str) -> usize {
+    stdout
+        .find(pattern)
+        .unwrap_or_else(|| panic!("'{pattern}' not found in:\n{stdout}"))
+}
+
+#[test]
+fn test_default_order() {
+    let output = run_prompt(&["--full-pwd", "--exit-code", "3"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let pwd = tests_dir().canonicalize().unwrap();
+
+    assert!(position(&stdout, pwd.to_str().unwrap()) < position(&stdout, "✘"));
+}
+
+#[test]
+fn test_custom_order() {
+    let output = run_prompt(&[
+        "--full-pwd",
+        "--exit-code",
+        "3",
+        "--order",
+        "exit_code,full_pwd",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let pwd = tests_dir().canonicalize().unwrap();
+
+    assert!(position(&stdout, "✘") < position(&stdout, pwd.to_str().unwrap()));
+}
+
+#[test]
+fn test_unknown_segment_in_order() {
+    let output = run_prompt(&["--order", "vcs,weather"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("unknown segment 'weather'"), "{stderr}");
+}
+
+#[test]
+fn test_template_optional_groups() {
+    let template = "[<{ssh}> ]{full_pwd}[ ({exit_code})]\\n❯ ";
+    let pwd = tests_dir().canonicalize().unwrap();
+
+    let output = run_prompt(&["--template", template, "--exit-code", "0"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(pwd.to_str().unwrap()), "{stdout}");
+    assert!(!stdout.contains('<') && !stdout.contains('('), "{stdout}");
+    assert!(stdout.ends_with("\n❯ "), "{stdout}");
+
+    let output = run_prompt(&["--template", template, "--exit-code", "4"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains('<'), "{stdout}");
+    assert!(position(&stdout, " (") < position(&stdout, "✘"), "{stdout}");
+    assert!(
+        position(&stdout, "✘") < position(&stdout, ")\n❯ "),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_shell_prompt() {
+    let output = run_prompt(&["--full-pwd", "--shell", "bash"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(
+        stdout.ends_with("\n$ ") || stdout.ends_with("\n# "),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_invalid_template() {
+    let output = run_prompt(&["--template", "{pwd} [{vcs}"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("unclosed '['"), "{stderr}");
+}
+
+#[test]
+fn test_right_prompt() {
+    let args = ["--full-pwd", "--exit-code", "3", "--right", "exit_code"];
+    let pwd = tests_dir().canonicalize().unwrap();
+
+    let output = run_prompt(&[&args[..], &["--shell", "zsh"]].concat());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(pwd.to_str().unwrap()), "{stdout}");
+    assert!(!stdout.contains('✘'), "{stdout}");
+
+    let output = run_prompt(&[&args[..], &["--shell", "zsh", "--rprompt"]].concat());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains(pwd.to_str().unwrap()), "{stdout}");
+    assert!(stdout.contains('✘'), "{stdout}");
+}
+
+#[test]
+fn test_bash_right_prompt() {
+    let output = prompt_command()
+        .env("COLUMNS", "80")
+        .args(["--full-pwd", "--exit-code", "3", "--right", "exit_code"])
+        .args(["--shell", "bash"])
+        .output()
+        .expect("Failed to execute auraline");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let pwd = tests_dir().canonicalize().unwrap();
+
+    // "✘ ³" is 3 columns wide
+    assert!(stdout.starts_with("\x01\x1b7\r\x1b[77C"), "{stdout:?}");
+    assert!(position(&stdout, "✘") < position(&stdout, "\x1b8\x02"));
+    assert!(position(&stdout, "\x1b8\x02") < position(&stdout, pwd.to_str().unwrap()));
+}
+
+/// Remove the non-printing regions delimited by `open` and `close`.
+fn strip_non_printing(s: &str, open: &str, close: &str) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find(open) {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find(close).expect("unterminated region") + start;
+        rest = &rest[end + close.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[test]
+fn test_non_printing_escapes() {
+    let args = ["--full-pwd", "--exit-code", "3", "--shell"];
+
+    let output = run_prompt(&[&args[..], &["bash"]].concat());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\x01\x1b[31;1m\x02✘"), "{stdout:?}");
+    assert!(!strip_non_printing(&stdout, "\x01", "\x02").contains('\x1b'));
+
+    let output = run_prompt(&[&args[..], &["zsh"]].concat());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("%{\x1b[31;1m%}✘"), "{stdout:?}");
+    assert!(!strip_non_printing(&stdout, "%{", "%}").contains('\x1b'));
+
+    for shell in ["fish", "plain"] {
+        let output = run_prompt(&[&args[..], &[shell]].concat());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("\x1b[31;1m✘"), "{stdout:?}");
+    }
+}
+
+/// Create an empty git repository, in the temporary directory of the tests, whose current
+/// branch is `branch`.
+fn git_repo(name: &str, branch: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    git(&dir, &["init", "--quiet"]);
+    git(&dir, &["checkout", "--quiet", "-b", branch]);
+    dir
+}
+
+fn run_prompt_in(dir: &PathBuf, args: &[&str]) -> String {
+    let output = prompt_command()
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .expect("Failed to execute auraline");
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_malicious_branch_bash() {
+    let repo = git_repo("branch_bash", "$(touch${IFS}pwned)`touch${IFS}pwned`");
+    let stdout = run_prompt_in(&repo, &["--vcs", "--shell", "bash"]);
+    assert!(
+        stdout.contains(r"\\$(touch\\${IFS}pwned)\\`touch\\${IFS}pwned\\`"),
+        "{stdout:?}"
+    );
+
+    // the prompt expanded by bash shows the name of the branch as it is
+    let output = Command::new("bash")
+        .current_dir(&repo)
+        .env("AURALINE_PS1", &stdout)
+        .args(["-c", r#"PS1=$AURALINE_PS1; printf '%s' "${PS1@P}""#])
+        .output()
+        .expect("Failed to execute bash");
+    let expanded = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        expanded.contains("$(touch${IFS}pwned)`touch${IFS}pwned`"),
+        "{expanded:?}"
+    );
+    assert!(!repo.join("pwned").exists());
+}
+
+#[test]
+fn test_bash_init_prompt_command() {
+    let script = Command::new(get_auraline_bin())
+        .args(["init", "bash"])
+        .output()
+        .expect("Failed to execute auraline")
+        .stdout;
+
+    // the prompt commands already set are kept, after that of auraline
+    for (before, after) in [
+        (
+            "PROMPT_COMMAND='history -a'",
+            r#"PROMPT_COMMAND="auraline_prompt_command; history -a""#,
+        ),
+        (
+            "PROMPT_COMMAND=('history -a')",
+            r#"PROMPT_COMMAND=([0]="auraline_prompt_command" [1]="history -a")"#,
+        ),
+    ] {
+        let output = Command::new("bash")
+            .env("AURALINE_INIT", String::from_utf8_lossy(&script).as_ref())
+            .args(["--norc", "-c"])
+            .arg(format!(
+                "{before}; eval \"$AURALINE_INIT\"; declare -p PROMPT_COMMAND"
+            ))
+            .output()
+            .expect("Failed to execute bash");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains(after), "{stdout}");
+    }
+}
+
+#[test]
+fn test_malicious_branch_zsh() {
+    let repo = git_repo("branch_zsh", "%F{red}100%");
+    let stdout = run_prompt_in(&repo, &["--vcs", "--shell", "zsh"]);
+    assert!(stdout.contains("%%F{red}100%%"), "{stdout:?}");
+
+    let stdout = run_prompt_in(&repo, &["--vcs", "--shell", "fish"]);
+    assert!(stdout.contains("%F{red}100%"), "{stdout:?}");
+}
+
+#[test]
+fn test_control_characters() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("evil\x1b]0;title\x07\x1b[2Jdir");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let stdout = run_prompt_in(&dir, &["--pwd", "--shell", "plain"]);
+    assert!(stdout.contains("evil]0;title[2Jdir"), "{stdout:?}");
+    assert!(!stdout.contains('\x07'), "{stdout:?}");
+}
+
+#[test]
+fn test_json_format() {
+    let output = run_prompt(&[
+        "--full-pwd",
+        "--ssh",
+        "--exit-code",
+        "3",
+        "--right",
+        "exit_code",
+        "--format",
+        "json",
+    ]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).expect("invalid JSON");
+    let pwd = tests_dir().canonicalize().unwrap();
+
+    let segments = json["segments"].as_array().unwrap();
+    assert_eq!(segments.len(), 3, "{json}");
+
+    let full_pwd = &segments[0];
+    assert_eq!(full_pwd["name"], "full_pwd");
+    assert_eq!(full_pwd["provider"], "basic::full_pwd");
+    assert_eq!(full_pwd["region"], "left");
+    assert_eq!(full_pwd["info"], pwd.to_str().unwrap());
+    assert_eq!(full_pwd["empty"], false);
+    assert_eq!(full_pwd["value"]["type"], "path");
+    assert_eq!(full_pwd["value"]["value"], pwd.to_str().unwrap());
+    assert!(full_pwd["elapsed_us"].is_u64());
+
+    let ssh = &segments[1];
+    assert_eq!(ssh["name"], "ssh");
+    assert_eq!(ssh["empty"], true);
+    assert!(ssh["info"].is_null());
+
+    let exit_code = &segments[2];
+    assert_eq!(exit_code["name"], "exit_code");
+    assert_eq!(exit_code["region"], "right");
+    assert_eq!(exit_code["icon"], "✘");
+    assert_eq!(exit_code["value"]["type"], "integer");
+    assert_eq!(exit_code["value"]["value"], 3);
+    assert!(json["elapsed_us"].is_u64());
+}
+
+#[test]
+fn test_json_status_value() {
+    let repo = git_repo("status_value", "main");
+    std::fs::write(repo.join("a.txt"), "a").unwrap();
+    std::fs::write(repo.join("b.txt"), "b").unwrap();
+    git(&repo, &["add", "a.txt"]);
+
+    let stdout = run_prompt_in(&repo, &["--vcs", "--format", "json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout).expect("invalid JSON");
+    let status = json["segments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["name"] == "vcs.status")
+        .expect("vcs.status not found");
+
+    assert_eq!(status["value"]["type"], "status", "{status}");
+    let counts = &status["value"]["value"];
+    assert_eq!(counts["staged"], 1, "{counts}");
+    assert_eq!(counts["added"], 1, "{counts}");
+    assert_eq!(counts["untracked"], 1, "{counts}");
+    assert_eq!(counts["modified"], 0, "{counts}");
+}
+
+#[test]
+fn test_timeout() {
+    let git = FakeGit::new("timeout", "sleep 5\n");
+    let begin = std::time::Instant::now();
+    let output = prompt_command()
+        .current_dir(&git.repo)
+        .env("PATH", git.path())
+        .args([
+            "--vcs",
+            "--full-pwd",
+            "--timeout",
+            "100ms",
+            "--format",
+            "json",
+            "--git-backend",
+            "cli",
+        ])
+        .output()
+        .expect("Failed to execute auraline");
+    assert!(begin.elapsed() < std::time::Duration::from_secs(3));
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).expect("invalid JSON");
+    for segment in json["segments"].as_array().unwrap() {
+        // the operation in progress, the remote, the partial clone and the last fetch are
+        // read from the repository, the submodules without a `.gitmodules` and the base
+        // without a base branch, without running git
+        let name = segment["name"].as_str().unwrap();
+        let vcs = name.starts_with("vcs.");
+        let read = matches!(
+            name,
+            "vcs.operation"
+                | "vcs.remote"
+                | "vcs.partial"
+                | "vcs.submodules"
+                | "vcs.base"
+                | "vcs.fetch"
+        );
+        assert_eq!(segment["timed_out"], vcs && !read, "{segment}");
+        assert_eq!(segment["empty"], vcs, "{segment}");
+    }
+
+    let output = prompt_command()
+        .current_dir(&git.repo)
+        .env("PATH", git.path())
+        .args([
+            "--vcs",
+            "--timeout",
+            "100ms",
+            "--timings",
+            "--git-backend",
+            "cli",
+        ])
+        .output()
+        .expect("Failed to execute auraline");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("::branch"), "{stdout}");
+    assert_eq!(stdout.matches("(timeout)").count(), 6, "{stdout}");
+}
+
+#[test]
+fn test_disk_cache() {
+    // a fake git logging its invocations
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("disk_cache");
+    let log = dir.join("git.log");
+    let git = FakeGit::new(
+        "disk_cache",
+        &format!("echo \"$1\" >> '{}'\necho v1.0\n", log.display()),
+    );
+    let repo = &git.repo;
+    std::fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+
+    let run = || {
+        let output = prompt_command()
+            .current_dir(repo)
+            .env("PATH", git.path())
+            .env("XDG_CACHE_HOME", git.dir.join("cache"))
+            .args(["--vcs", "--disk-cache", "--git-backend", "cli"])
+            .output()
+            .expect("Failed to execute auraline");
+        assert!(String::from_utf8_lossy(&output.stdout).contains("v1.0"));
+        let calls = std::fs::read_to_string(&log).unwrap_or_default();
+        std::fs::remove_file(&log).unwrap();
+        calls
+    };
+
+    // the file of a repository not visited for long
+    let outputs = git.dir.join("cache/auraline/outputs");
+    std::fs::create_dir_all(&outputs).unwrap();
+    let stale = std::fs::File::create(outputs.join("0123456789abcdef.json")).unwrap();
+    stale
+        .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(30 * 86400))
+        .unwrap();
+
+    let calls = run();
+    assert!(
+        calls.contains("describe") && calls.contains("status"),
+        "{calls}"
+    );
+
+    // the outputs of the repository are kept in a single file, the stale one is removed
+    let files = std::fs::read_dir(&outputs)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.ends_with(".json"))
+        .collect::<Vec<_>>();
+    assert_eq!(files.len(), 1, "{files:?}");
+    assert_ne!(files[0], "0123456789abcdef.json");
+
+    // the outputs depending only on the state of the repository are persisted
+    let calls = run();
+    assert!(
+        !calls.contains("describe") && !calls.contains("name-rev"),
+        "{calls}"
+    );
+    assert!(
+        !calls.contains("rev-parse") && calls.contains("status"),
+        "{calls}"
+    );
+
+    // until the state of the repository changes
+    let head = std::fs::File::options()
+        .append(true)
+        .open(repo.join(".git/HEAD"))
+        .unwrap();
+    head.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(1))
+        .unwrap();
+    let calls = run();
+    assert!(
+        calls.contains("describe") && calls.contains("name-rev"),
+        "{calls}"
+    );
+}
+
+#[test]
+fn test_exec_policy() {
+    // a fake git showing its environment as the branch, hanging on `describe` and
+    // failing otherwise
+    let script = "case \"$1\" in\n\
+        status) printf '# branch.head %s,%s,%s,%s,\\377\\n' \"$LC_ALL\" \"$GIT_OPTIONAL_LOCKS\" \
+        \"$GIT_TERMINAL_PROMPT\" \"$AURALINE_TEST\";;\n\
+        describe) exec sleep 5;;\n\
+        *) echo \"fatal: $1\" >&2; exit 128;;\n\
+        esac\n";
+    let git = FakeGit::new("exec_policy", script);
+
+    let begin = std::time::Instant::now();
+    let output = prompt_command()
+        .current_dir(&git.repo)
+        .env("PATH", git.path())
+        .env("AURALINE_CONFIG", tests_dir().join("configs/exec.toml"))
+        .args(["--debug", "--git-backend", "cli"])
+        .output()
+        .expect("Failed to execute auraline");
+    assert!(begin.elapsed() < std::time::Duration::from_secs(3));
+
+    // the output is decoded lossily, in the environment of the policy
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("C,0,0,exec,\u{FFFD}"), "{stdout}");
+
+    // the failures are reported on stderr
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("killed after 100ms"), "{stderr}");
+    assert!(stderr.contains("fatal: "), "{stderr}");
+
+    // and only in debug mode
+    let output = prompt_command()
+        .current_dir(&git.repo)
+        .env("PATH", git.path())
+        .env("AURALINE_CONFIG", tests_dir().join("configs/exec.toml"))
+        .args(["--git-backend", "cli"])
+        .output()
+        .expect("Failed to execute auraline");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_git_backend() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("git_backend");
+    let _ = std::fs::remove_dir_all(&dir);
+    let (origin, repo) = (dir.join("origin"), dir.join("repo"));
+    std::fs::create_dir_all(&origin).unwrap();
+
+    git(&origin, &["init", "--quiet", "-b", "main"]);
+    git(
+        &origin,
+        &["commit", "--quiet", "--allow-empty", "-m", "one"],
+    );
+    git(&origin, &["tag", "-a", "-m", "v1", "v1.0"]);
+    git(
+        &origin,
+        &["commit", "--quiet", "--allow-empty", "-m", "two"],
+    );
+    git(&dir, &["clone", "--quiet", "origin", "repo"]);
+    git(&repo, &["reset", "--quiet", "--hard", "HEAD~1"]);
+    for msg in ["three", "four", "five"] {
+        git(&repo, &["commit", "--quiet", "--allow-empty", "-m", msg]);
+    }
+    // a lightweight tag on a merged branch, nearer than the annotated one
+    git(&repo, &["checkout", "--quiet", "-b", "side", "HEAD~2"]);
+    for msg in ["six", "seven", "eight"] {
+        git(&repo, &["commit", "--quiet", "--allow-empty", "-m", msg]);
+        if msg == "six" {
+            git(&repo, &["tag", "v1.1"]);
+        }
+    }
+    git(&repo, &["checkout", "--quiet", "main"]);
+    git(
+        &repo,
+        &["merge", "--quiet", "--no-ff", "-m", "merge", "side"],
+    );
+    std::fs::write(repo.join("file"), "stashed").unwrap();
+    git(&repo, &["add", "file"]);
+    git(&repo, &["stash", "--quiet"]);
+    git(&repo, &["gc", "--quiet"]);
+
+    let run = |backend: &str| {
+        let output = prompt_command()
+            .current_dir(&repo)
+            .args(["--vcs", "--format", "json", "--git-backend", backend])
+            .output()
+            .expect("Failed to execute auraline");
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        json["segments"]
+            .as_array()
+            .unwrap()
+            .iter()
+            // the ages change between the runs
+            .filter(|s| s["name"] != "vcs.age" && s["name"] != "vcs.fetch")
+            .map(|s| (s["name"].clone(), s["info"].clone(), s["value"].clone()))
+            .collect::<Vec<_>>()
+    };
+
+    let native = run("native");
+    assert_eq!(native, run("cli"));
+    let info = |name: &str| {
+        native
+            .iter()
+            .find(|(n, _, _)| n == name)
+            .map(|(_, info, _)| info.as_str().unwrap().to_owned())
+            .unwrap()
+    };
+    assert_eq!(info("vcs.branch"), "main");
+    // the commits counted by the walk of `git describe` depend on their dates, and
+    // those of the tests may tie or not
+    assert!(info("vcs.commit").starts_with("v1.1▴"));
+    assert_eq!(info("vcs.stash"), "≡¹");
+    assert_eq!(info("vcs.divergence"), "↑7↓1");
+}
+
+#[test]
+fn test_fetch_age() {
+    let repo = git_repo("fetch_age", "main");
+    let fetch_head = repo.join(".git/FETCH_HEAD");
+    std::fs::write(&fetch_head, "").unwrap();
+    let config = repo.join(".git/auraline.toml");
+    std::fs::write(
+        &config,
+        "[fetch]\nmin = \"1d\"\nstale = \"3d\"\n\n\
+        [style.\"vcs.fetch\"]\ninfo = \"dimmed\"\nalert = \"red\"\n",
+    )
+    .unwrap();
+
+    let fetched = |days: u64| {
+        let ago = std::time::Duration::from_secs(days * 86400 + 60);
+        std::fs::File::options()
+            .write(true)
+            .open(&fetch_head)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - ago)
+            .unwrap();
+        let output = prompt_command()
+            .current_dir(&repo)
+            .env("AURALINE_CONFIG", &config)
+            .args(["--vcs", "--template", "{vcs.fetch}"])
+            .output()
+            .expect("Failed to execute auraline");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    // hidden below the threshold, highlighted past the staleness limit
+    assert!(!fetched(0).contains('⟳'));
+    let stdout = fetched(2);
+    assert!(
+        stdout.contains("⟳") && stdout.contains("\x1b[2m2d\x1b[0m"),
+        "{stdout:?}"
+    );
+    let stdout = fetched(5);
+    assert!(stdout.contains("\x1b[31m5d\x1b[0m"), "{stdout:?}");
+}
+
+#[test]
+fn test_auto_fetch() {
+    use std::time::Duration;
+
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("auto_fetch");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let (work, repo) = (dir.join("work"), dir.join("repo"));
+    let fetch_dir = dir.join("cache/auraline/fetch");
+
+    // a local bare repository as the remote, pushed to from another clone
+    git(
+        &dir,
+        &["init", "--quiet", "--bare", "-b", "main", "origin.git"],
+    );
+    git(&dir, &["clone", "--quiet", "origin.git", "work"]);
+    let push = |msg: &str| {
+        git(&work, &["commit", "--quiet", "--allow-empty", "-m", msg]);
+        git(&work, &["push", "--quiet", "origin", "main"]);
+        git(&work, &["rev-parse", "HEAD"])
+    };
+    push("one");
+    git(&dir, &["clone", "--quiet", "origin.git", "repo"]);
+
+    let prompt = || {
+        let output = prompt_command()
+            .current_dir(&repo)
+            .env("XDG_CACHE_HOME", dir.join("cache"))
+            .args(["--vcs", "--auto-fetch", "1h"])
+            .output()
+            .expect("Failed to execute auraline");
+        assert!(String::from_utf8_lossy(&output.stdout).contains("main"));
+    };
+    let fetched = || git(&repo, &["rev-parse", "origin/main"]);
+    let wait_for = |head: &str| {
+        for _ in 0..100 {
+            if fetched() == head && !fetch_dir.join(lock_name(&fetch_dir)).exists() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        panic!("origin/main not fetched");
+    };
+
+    // the remote is fetched in the background
+    let head = push("two");
+    prompt();
+    wait_for(&head);
+
+    // at most once per interval
+    let stale = fetched();
+    push("three");
+    prompt();
+    std::thread::sleep(Duration::from_millis(500));
+    assert_eq!(fetched(), stale);
+
+    // nor while another fetch holds the lock
+    let lock = fetch_dir.join(lock_name(&fetch_dir));
+    std::fs::remove_file(lock.with_extension("stamp")).unwrap();
+    std::fs::write(&lock, "").unwrap();
+    prompt();
+    std::thread::sleep(Duration::from_millis(500));
+    assert_eq!(fetched(), stale);
+
+    std::fs::remove_file(&lock).unwrap();
+    let head = push("four");
+    prompt();
+    wait_for(&head);
+}
+
+/// The name of the lock of the single repository with a fetch stamp in `dir`.
+fn lock_name(dir: &std::path::Path) -> String {
+    let stamp = std::fs::read_dir(dir)
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .find(|name| name.ends_with(".stamp"))
+        .expect("no fetch stamp");
+    stamp.replace(".stamp", ".lock")
+}
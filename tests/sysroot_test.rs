@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn get_auraline_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_auraline"))
+}
+
+fn sysroot(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("sysroots")
+        .join(name)
+}
+
+fn run_prompt_in(sysroot: &PathBuf, args: &[&str]) -> String {
+    let output = Command::new(get_auraline_bin())
+        .env("XDG_CONFIG_HOME", sysroot)
+        .env("AURALINE_SYSROOT", sysroot)
+        .env_remove("AURALINE_CONFIG")
+        .env_remove("AURALINE_PROFILE")
+        .env_remove("AURALINE_OPTIONS")
+        .arg("prompt")
+        .args(args)
+        .output()
+        .expect("Failed to execute auraline");
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_huge_pages_numa() {
+    let stdout = run_prompt_in(&sysroot("numa"), &["--huge-pages"]);
+    assert!(stdout.contains("1024x2MB,2x1GB|512x2MB"), "{stdout}");
+}
+
+#[test]
+fn test_huge_pages_flat() {
+    let stdout = run_prompt_in(&sysroot("flat"), &["--huge-pages"]);
+    assert!(stdout.contains("256x2MB"), "{stdout}");
+    assert!(!stdout.contains("GB"), "{stdout}");
+}
+
+#[test]
+fn test_huge_pages_none() {
+    // a sysroot without hugepages at all
+    let stdout = run_prompt_in(&sysroot("flat").join("etc"), &["--huge-pages"]);
+    assert!(!stdout.contains("x2MB"), "{stdout}");
+}
+
+#[test]
+fn test_os() {
+    let stdout = run_prompt_in(&sysroot("flat"), &["--os", "--nerd-font"]);
+    assert!(stdout.contains(""), "{stdout}");
+}
+
+#[test]
+fn test_memory() {
+    let stdout = run_prompt_in(&sysroot("flat"), &["--memory"]);
+    assert!(stdout.contains("25.0%"), "{stdout}");
+}
+
+#[test]
+fn test_sysroot_option() {
+    // the command line takes precedence over AURALINE_SYSROOT
+    let stdout = run_prompt_in(
+        &sysroot("flat"),
+        &[
+            "--huge-pages",
+            "--sysroot",
+            sysroot("numa").to_str().unwrap(),
+        ],
+    );
+    assert!(stdout.contains("512x2MB"), "{stdout}");
+}